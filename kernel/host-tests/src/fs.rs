@@ -0,0 +1,11 @@
+// Only the parts of the VFS that don't use the global state of the kernel
+#[path = "../../rust-kernel/src/fs/vfs.rs"]
+pub mod vfs;
+#[path = "../../rust-kernel/src/fs/mount.rs"]
+pub mod mount;
+#[path = "../../rust-kernel/src/fs/ustar.rs"]
+pub mod ustar;
+#[path = "../../rust-kernel/src/fs/cpio.rs"]
+pub mod cpio;
+#[path = "../../rust-kernel/src/fs/tmpfs.rs"]
+pub mod tmpfs;
@@ -0,0 +1,42 @@
+// The kernel modules below are compiled for the host. The functions of the C kernel
+// that they use are replaced by the ones at the end of this file
+extern crate alloc;
+
+pub mod fs;
+
+#[path = "../../rust-kernel/src/compression/mod.rs"]
+pub mod compression;
+#[path = "../../rust-kernel/src/cmdline.rs"]
+pub mod cmdline;
+#[path = "../../rust-kernel/src/block/mod.rs"]
+pub mod block;
+
+use std::{alloc::{alloc_zeroed, dealloc, Layout}, ffi::c_void, sync::atomic::{AtomicUsize, Ordering}};
+
+const PAGE_SIZE: usize = 4096;
+
+// Pages allocated and not freed yet, to check that nothing leaks
+pub static ALLOCATED_PAGES: AtomicUsize = AtomicUsize::new(0);
+
+fn page_layout(page_count: usize) -> Layout{
+    Layout::from_size_align(page_count * PAGE_SIZE, PAGE_SIZE).unwrap()
+}
+
+// The physical addresses are the same as the virtual ones. They are unsafe like the C
+// functions that they replace
+#[allow(clippy::missing_safety_doc)]
+pub unsafe fn alloc_page_phys_addr(page_count: usize) -> *mut c_void{
+    ALLOCATED_PAGES.fetch_add(page_count, Ordering::SeqCst);
+    unsafe { alloc_zeroed(page_layout(page_count)) as *mut c_void }
+}
+
+#[allow(clippy::missing_safety_doc)]
+pub unsafe fn phys_addr_to_limine_virtual_addr(phys_addr: usize) -> usize{
+    phys_addr
+}
+
+#[allow(clippy::missing_safety_doc)]
+pub unsafe fn free_pages(pointer: *mut c_void, page_count: usize){
+    ALLOCATED_PAGES.fetch_sub(page_count, Ordering::SeqCst);
+    unsafe { dealloc(pointer as *mut u8, page_layout(page_count)) };
+}
@@ -0,0 +1,53 @@
+use std::sync::Mutex;
+
+use host_tests::cmdline::{self, Console, LogLevel, ParseError};
+
+// The configuration is global, the tests must not change it at the same time
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[test]
+fn defaults_are_used_without_options(){
+    let _lock = LOCK.lock().unwrap();
+    assert!(cmdline::init("").is_empty());
+    assert_eq!(cmdline::get_init(), "init.elf");
+    assert_eq!(cmdline::get_loglevel(), LogLevel::Info);
+    assert_eq!(cmdline::get_console(), Console::Screen);
+    assert!(cmdline::is_smp_enabled());
+}
+
+#[test]
+fn known_options_are_parsed(){
+    let _lock = LOCK.lock().unwrap();
+    assert!(cmdline::init("  init=/bin/sh loglevel=debug\tconsole=serial smp=off ").is_empty());
+    assert_eq!(cmdline::get_init(), "/bin/sh");
+    assert_eq!(cmdline::get_loglevel(), LogLevel::Debug);
+    assert_eq!(cmdline::get_console(), Console::Serial);
+    assert!(!cmdline::is_smp_enabled());
+}
+
+#[test]
+fn unknown_options_are_kept(){
+    let _lock = LOCK.lock().unwrap();
+    assert!(cmdline::init("keyboard=fr quiet path=a=b").is_empty());
+    assert_eq!(cmdline::get("keyboard").as_deref(), Some("fr"));
+    assert_eq!(cmdline::get("quiet").as_deref(), Some(""));
+    assert_eq!(cmdline::get("path").as_deref(), Some("a=b"));
+    assert_eq!(cmdline::get("missing"), None);
+}
+
+#[test]
+fn invalid_values_are_ignored_and_returned(){
+    let _lock = LOCK.lock().unwrap();
+    let errors = cmdline::init("loglevel=loud smp=maybe init= console=serial");
+    let keys: Vec<&str> = errors.iter().map(|ParseError::InvalidValue { key, .. }| key.as_str()).collect();
+    assert_eq!(keys, ["loglevel", "smp", "init"]);
+    assert_eq!(cmdline::get_loglevel(), LogLevel::Info);
+    assert!(cmdline::is_smp_enabled());
+    assert_eq!(cmdline::get_init(), "init.elf");
+    assert_eq!(cmdline::get_console(), Console::Serial);
+}
+
+#[test]
+fn log_levels_are_ordered_by_verbosity(){
+    assert!(LogLevel::Error < LogLevel::Warning && LogLevel::Warning < LogLevel::Info && LogLevel::Info < LogLevel::Debug);
+}
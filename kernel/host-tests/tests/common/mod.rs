@@ -0,0 +1,56 @@
+#![allow(dead_code)]
+
+use host_tests::fs::{ustar, vfs::{MountPoint, PathBuf}};
+
+pub enum Entry<'a>{
+    File(&'a str, &'a [u8]),
+    Folder(&'a str),
+    SymLink(&'a str, &'a str),
+    HardLink(&'a str, &'a str),
+}
+
+// The drivers read the archives in place, so they must live until the end of the tests
+pub fn leak(data: Vec<u8>) -> &'static [u8]{
+    data.leak()
+}
+
+pub fn tar_archive(entries: &[Entry]) -> &'static [u8]{
+    let mut builder = tar::Builder::new(Vec::new());
+    for entry in entries{
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        let (path, data): (&str, &[u8]) = match entry{
+            Entry::File(path, data) => {
+                header.set_entry_type(tar::EntryType::Regular);
+                (path, data)
+            },
+            Entry::Folder(path) => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(0o755);
+                (path, &[])
+            },
+            Entry::SymLink(path, target) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_link_name(target).unwrap();
+                (path, &[])
+            },
+            Entry::HardLink(path, target) => {
+                header.set_entry_type(tar::EntryType::Link);
+                header.set_link_name(target).unwrap();
+                (path, &[])
+            },
+        };
+        header.set_size(data.len() as u64);
+        builder.append_data(&mut header, path, data).unwrap();
+    }
+    leak(builder.into_inner().unwrap())
+}
+
+pub fn ustar_fs(data: &'static [u8]) -> MountPoint{
+    ustar::headers_to_fs(ustar::parse_file(data).unwrap(), data).unwrap()
+}
+
+pub fn read_all(filesystem: &MountPoint, path: &str) -> Vec<u8>{
+    let size = filesystem.get_size(PathBuf::from(path)).unwrap();
+    filesystem.read(PathBuf::from(path), 0, size).unwrap().into_vec()
+}
@@ -0,0 +1,86 @@
+use std::io::Write;
+
+use flate2::{Compression, write::GzEncoder};
+use host_tests::compression::{self, Error, gzip, lz4};
+
+// Text with repetitions and some noise, so that every kind of deflate block is used
+fn sample(len: usize) -> Vec<u8>{
+    let mut state: u32 = 1;
+    (0..len).map(|i| {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        if i % 7 == 0 { (state >> 24) as u8 } else { b"the quick brown fox "[i % 20] }
+    }).collect()
+}
+
+fn gzip(data: &[u8], level: u32) -> Vec<u8>{
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level));
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn lz4_frame(data: &[u8]) -> Vec<u8>{
+    let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn gzip_round_trips_at_every_level(){
+    let data = sample(200_000);
+    for level in 0..=9{
+        let output = gzip::decompress(&gzip(&data, level)).unwrap();
+        assert_eq!(output.as_slice(), &data[..], "level {}", level);
+    }
+}
+
+#[test]
+fn gzip_members_are_concatenated(){
+    let mut stream = gzip(b"first ", 6);
+    stream.extend(gzip(b"", 6));
+    stream.extend(gzip(b"second", 1));
+    assert_eq!(gzip::decompress(&stream).unwrap().as_slice(), b"first second");
+}
+
+#[test]
+fn gzip_corruption_is_detected(){
+    let mut stream = gzip(&sample(10_000), 6);
+    let crc = stream.len() - 8;
+    stream[crc] ^= 0xff;
+    assert_eq!(gzip::decompress(&stream).err(), Some(Error::ChecksumMismatch));
+    let stream = gzip(&sample(10_000), 6);
+    assert_eq!(gzip::decompress(&stream[..stream.len() / 2]).err(), Some(Error::Truncated));
+}
+
+#[test]
+fn crc32_matches_the_reference(){
+    assert_eq!(gzip::crc32(b"123456789"), 0xcbf43926);
+}
+
+#[test]
+fn lz4_frames_round_trip(){
+    for len in [0, 1, 1000, 300_000]{
+        let data = sample(len);
+        assert_eq!(lz4::decompress(&lz4_frame(&data)).unwrap().as_slice(), &data[..], "length {}", len);
+    }
+}
+
+#[test]
+fn lz4_skippable_frames_are_ignored(){
+    let mut stream = vec![0x50, 0x2a, 0x4d, 0x18, 3, 0, 0, 0, 1, 2, 3];
+    stream.extend(lz4_frame(b"after"));
+    assert_eq!(lz4::decompress(&stream).unwrap().as_slice(), b"after");
+}
+
+#[test]
+fn lz4_truncation_is_detected(){
+    let stream = lz4_frame(&sample(50_000));
+    assert!(lz4::decompress(&stream[..stream.len() / 2]).is_err());
+}
+
+#[test]
+fn decompress_detects_the_format(){
+    let data = sample(5000);
+    assert_eq!(compression::decompress(&gzip(&data, 9)).unwrap().unwrap().as_slice(), &data[..]);
+    assert_eq!(compression::decompress(&lz4_frame(&data)).unwrap().unwrap().as_slice(), &data[..]);
+    assert!(compression::decompress(b"070701 not compressed").unwrap().is_none());
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{leak, read_all};
+use host_tests::fs::{cpio::{self, ParseError}, vfs::PathBuf};
+
+const FILE: u32 = 0o100644;
+const FOLDER: u32 = 0o040755;
+const SYMLINK: u32 = 0o120777;
+
+// newc archive like the one made by cpio -H newc, entries are (name, mode, ino, nlink, data)
+fn cpio_archive(entries: &[(&str, u32, u32, u32, &[u8])]) -> Vec<u8>{
+    let mut archive = Vec::new();
+    let trailer: (&str, u32, u32, u32, &[u8]) = ("TRAILER!!!", 0, 0, 1, &[]);
+    for (name, mode, ino, nlink, data) in entries.iter().chain([&trailer]){
+        let fields = [*ino, *mode, 1000, 100, *nlink, 1_700_000_000, data.len() as u32, 0, 1, 0, 0, name.len() as u32 + 1, 0];
+        archive.extend_from_slice(b"070701");
+        for field in fields{
+            archive.extend_from_slice(format!("{:08x}", field).as_bytes());
+        }
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+    }
+    archive
+}
+
+#[test]
+fn files_folders_and_metadata_are_loaded(){
+    let data = leak(cpio_archive(&[
+        (".", FOLDER, 1, 2, b""),
+        ("bin", FOLDER, 2, 2, b""),
+        ("bin/init.elf", FILE, 3, 1, b"\x7fELF"),
+        ("etc/motd", FILE, 4, 1, b"hello"),
+    ]));
+    assert!(cpio::is_cpio(data));
+    let filesystem = cpio::parse_file(data).unwrap();
+    assert_eq!(read_all(&filesystem, "/bin/init.elf"), b"\x7fELF");
+    assert_eq!(read_all(&filesystem, "/etc/motd"), b"hello");
+    let metadata = filesystem.get_metadata(PathBuf::from("bin/init.elf")).unwrap();
+    assert_eq!((metadata.mode, metadata.uid, metadata.gid, metadata.mtime), (0o644, 1000, 100, 1_700_000_000));
+    assert_eq!(&*filesystem.read(PathBuf::from("etc/motd"), 3, usize::MAX).unwrap(), b"lo");
+}
+
+#[test]
+fn symlinks_take_their_target_from_the_data(){
+    let data = leak(cpio_archive(&[("file", FILE, 1, 1, b"x"), ("link", SYMLINK, 2, 1, b"file")]));
+    let filesystem = cpio::parse_file(data).unwrap();
+    let link = filesystem.get_root().find(PathBuf::from("link")).unwrap();
+    assert_eq!(link.get_symlink_target(), Some("file"));
+}
+
+#[test]
+fn hard_links_share_the_data_stored_once(){
+    // Like GNU cpio, only the last name of the file has the data
+    let data = leak(cpio_archive(&[("first", FILE, 7, 2, b""), ("second", FILE, 7, 2, b"shared")]));
+    let filesystem = cpio::parse_file(data).unwrap();
+    assert_eq!(read_all(&filesystem, "/first"), b"shared");
+    assert_eq!(read_all(&filesystem, "/second"), b"shared");
+}
+
+#[test]
+fn invalid_archives_are_errors(){
+    let archive = cpio_archive(&[("file", FILE, 1, 1, b"data")]);
+    let truncated = leak(archive[..archive.len() - 130].to_vec());
+    assert!(matches!(cpio::parse_file(truncated), Err(ParseError::Truncated { .. })));
+
+    let mut invalid_number = archive.clone();
+    invalid_number[6] = b'z';
+    assert!(matches!(cpio::parse_file(leak(invalid_number)), Err(ParseError::InvalidNumber { field: "ino", .. })));
+
+    let nested = leak(cpio_archive(&[("file", FILE, 1, 1, b"x"), ("file/inner", FILE, 2, 1, b"y")]));
+    assert!(matches!(cpio::parse_file(nested), Err(ParseError::NotAFolder { .. })));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{Entry, read_all, tar_archive, ustar_fs};
+use host_tests::fs::{mount::MountTable, tmpfs, vfs::{Error, PathBuf}};
+
+fn path(path: &str) -> PathBuf{
+    PathBuf::from(path)
+}
+
+fn root_table() -> MountTable{
+    let mut mounts = MountTable::new();
+    let root = ustar_fs(tar_archive(&[
+        Entry::Folder("mnt/"),
+        Entry::Folder("mnt/data/"),
+        Entry::File("mnt/data/hidden", b"root"),
+        Entry::File("file", b"file"),
+        Entry::SymLink("link", "mnt/data"),
+        Entry::SymLink("absolute", "/mnt"),
+        Entry::SymLink("loop", "loop"),
+    ]));
+    mounts.mount(PathBuf::new(), root).unwrap();
+    mounts
+}
+
+#[test]
+fn resolve_uses_the_longest_mounted_prefix(){
+    let mut mounts = root_table();
+    mounts.mount(path("/mnt"), tmpfs::new_tmpfs()).unwrap();
+    mounts.mount(path("/mnt/data"), ustar_fs(tar_archive(&[Entry::File("inner", b"data")]))).unwrap();
+
+    let (_, relative) = mounts.resolve(path("/file")).unwrap();
+    assert_eq!(relative, path("/file"));
+    let (_, relative) = mounts.resolve(path("/mnt/other")).unwrap();
+    assert_eq!(relative, path("/other"));
+    let (filesystem, relative) = mounts.resolve(path("/mnt/data/inner")).unwrap();
+    assert_eq!(relative, path("/inner"));
+    assert_eq!(read_all(filesystem, "/inner"), b"data");
+}
+
+#[test]
+fn mount_checks_the_target(){
+    let mut mounts = root_table();
+    assert!(matches!(mounts.mount(PathBuf::new(), tmpfs::new_tmpfs()), Err(Error::Busy)));
+    assert!(matches!(mounts.mount(path("/file"), tmpfs::new_tmpfs()), Err(Error::NotAFolder)));
+    assert!(matches!(mounts.mount(path("/file/sub"), tmpfs::new_tmpfs()), Err(Error::NotAFolder)));
+    assert!(matches!(mounts.mount(path("/missing/sub"), tmpfs::new_tmpfs()), Err(Error::NotFound)));
+    // A missing target in an existing folder is allowed
+    mounts.mount(path("/new"), tmpfs::new_tmpfs()).unwrap();
+    assert!(mounts.is_mountpoint(&path("/new")));
+}
+
+#[test]
+fn mounted_children_are_listed(){
+    let mut mounts = root_table();
+    mounts.mount(path("/mnt"), tmpfs::new_tmpfs()).unwrap();
+    mounts.mount(path("/mnt/data"), tmpfs::new_tmpfs()).unwrap();
+    let children = mounts.get_mounted_children(&PathBuf::new());
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0].get_name(), "mnt");
+    assert!(children[0].is_folder());
+}
+
+#[test]
+fn umount_refuses_busy_file_systems(){
+    let mut mounts = root_table();
+    mounts.mount(path("/mnt"), tmpfs::new_tmpfs()).unwrap();
+    mounts.mount(path("/mnt/data"), tmpfs::new_tmpfs()).unwrap();
+    assert!(matches!(mounts.umount(&PathBuf::new()), Err(Error::Busy)));
+    assert!(matches!(mounts.umount(&path("/mnt")), Err(Error::Busy)));
+    assert!(matches!(mounts.umount(&path("/file")), Err(Error::NotAMountpoint)));
+    mounts.umount(&path("/mnt/data")).unwrap();
+    mounts.umount(&path("/mnt")).unwrap();
+    assert!(!mounts.is_mountpoint(&path("/mnt")));
+}
+
+#[test]
+fn canonicalize_follows_symlinks(){
+    let mounts = root_table();
+    assert_eq!(mounts.canonicalize(path("/link/hidden"), true).unwrap(), path("/mnt/data/hidden"));
+    assert_eq!(mounts.canonicalize(path("/absolute/data"), true).unwrap(), path("/mnt/data"));
+    // The last component is kept unless follow_last
+    assert_eq!(mounts.canonicalize(path("/link"), false).unwrap(), path("/link"));
+    assert_eq!(mounts.canonicalize(path("/link"), true).unwrap(), path("/mnt/data"));
+    // Missing components at the end are allowed
+    assert_eq!(mounts.canonicalize(path("/link/new"), true).unwrap(), path("/mnt/data/new"));
+}
+
+#[test]
+fn canonicalize_stops_on_symlink_loops(){
+    let mounts = root_table();
+    assert!(matches!(mounts.canonicalize(path("/loop"), true), Err(Error::TooManyLinks)));
+}
@@ -0,0 +1,130 @@
+use std::sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}};
+
+use host_tests::{ALLOCATED_PAGES, block::{BlockDevice, Error, SECTOR_SIZE, cache::{BLOCK_SIZE, PageCache}, memory_disk::MemoryDisk}};
+
+// The page counter is global, the tests must not allocate at the same time
+static LOCK: Mutex<()> = Mutex::new(());
+
+// Disk in memory that counts the sector reads and can be inspected by the test
+struct TestDisk{
+    data: Arc<Mutex<Vec<u8>>>,
+    reads: Arc<AtomicUsize>,
+}
+
+impl BlockDevice for TestDisk{
+    fn get_sector_count(&self) -> u64{
+        (self.data.lock().unwrap().len() / SECTOR_SIZE) as u64
+    }
+
+    fn read_sectors(&mut self, sector: u64, buffer: &mut [u8]) -> Result<(), Error>{
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        let start = sector as usize * SECTOR_SIZE;
+        buffer.copy_from_slice(&self.data.lock().unwrap()[start..start + buffer.len()]);
+        Ok(())
+    }
+
+    fn write_sectors(&mut self, sector: u64, data: &[u8]) -> Result<(), Error>{
+        let start = sector as usize * SECTOR_SIZE;
+        self.data.lock().unwrap()[start..start + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+}
+
+fn pattern(len: usize) -> Vec<u8>{
+    (0..len).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+// The last block is partial
+fn test_disk(sectors: usize) -> (TestDisk, Arc<Mutex<Vec<u8>>>, Arc<AtomicUsize>){
+    let data = Arc::new(Mutex::new(pattern(sectors * SECTOR_SIZE)));
+    let reads = Arc::new(AtomicUsize::new(0));
+    (TestDisk { data: data.clone(), reads: reads.clone() }, data, reads)
+}
+
+#[test]
+fn reads_are_served_from_the_cache(){
+    let _lock = LOCK.lock().unwrap();
+    let (disk, data, reads) = test_disk(101);
+    let mut cache = PageCache::new();
+    let device = cache.add_device(Box::new(disk));
+    let mut buffer = vec![0; 10_000];
+    assert_eq!(cache.read(device, 4000, &mut buffer).unwrap(), 10_000);
+    assert_eq!(buffer, data.lock().unwrap()[4000..14_000]);
+    let device_reads = reads.load(Ordering::SeqCst);
+    cache.read(device, 5000, &mut buffer[..100]).unwrap();
+    assert_eq!(reads.load(Ordering::SeqCst), device_reads);
+    assert_eq!(cache.with_block(device, 1, |block| block.len()).unwrap(), BLOCK_SIZE);
+}
+
+#[test]
+fn reads_stop_at_the_end_of_the_device(){
+    let _lock = LOCK.lock().unwrap();
+    let (disk, data, _) = test_disk(101);
+    let size = 101 * SECTOR_SIZE as u64;
+    let mut cache = PageCache::new();
+    let device = cache.add_device(Box::new(disk));
+    let mut buffer = vec![0; 1000];
+    assert_eq!(cache.read(device, size - 100, &mut buffer).unwrap(), 100);
+    assert_eq!(buffer[..100], data.lock().unwrap()[size as usize - 100..]);
+    assert_eq!(cache.read(device, size + 5, &mut buffer).unwrap(), 0);
+    assert_eq!(cache.with_block(device, 100, |_| ()).err(), Some(Error::OutOfRange));
+}
+
+#[test]
+fn writes_reach_the_device_on_sync(){
+    let _lock = LOCK.lock().unwrap();
+    let (disk, data, _) = test_disk(64);
+    let mut cache = PageCache::new();
+    let device = cache.add_device(Box::new(disk));
+    // Across two blocks
+    assert_eq!(cache.write(device, BLOCK_SIZE as u64 - 5, b"hello world").unwrap(), 11);
+    assert_ne!(&data.lock().unwrap()[BLOCK_SIZE - 5..BLOCK_SIZE + 6], b"hello world");
+    let mut buffer = [0; 11];
+    cache.read(device, BLOCK_SIZE as u64 - 5, &mut buffer).unwrap();
+    assert_eq!(&buffer, b"hello world");
+    cache.sync(device).unwrap();
+    assert_eq!(&data.lock().unwrap()[BLOCK_SIZE - 5..BLOCK_SIZE + 6], b"hello world");
+    // The rest of the blocks is unchanged
+    assert_eq!(data.lock().unwrap()[..BLOCK_SIZE - 5], pattern(BLOCK_SIZE - 5)[..]);
+}
+
+#[test]
+fn least_recently_used_blocks_are_evicted_and_written_back(){
+    let _lock = LOCK.lock().unwrap();
+    let pages_before = ALLOCATED_PAGES.load(Ordering::SeqCst);
+    // 6 MiB is more than the cache can hold
+    let (disk, data, reads) = test_disk(12_288);
+    let size = 12_288 * SECTOR_SIZE;
+    let mut cache = PageCache::new();
+    let device = cache.add_device(Box::new(disk));
+    cache.write(device, 0, &vec![1; size]).unwrap();
+    assert!(ALLOCATED_PAGES.load(Ordering::SeqCst) - pages_before <= 1024);
+    // The first blocks were written back when they were evicted
+    assert!(data.lock().unwrap()[..BLOCK_SIZE].iter().all(|byte| *byte == 1));
+    let mut buffer = vec![0; size];
+    cache.read(device, 0, &mut buffer).unwrap();
+    assert!(buffer.iter().all(|byte| *byte == 1));
+
+    // A block used recently stays in the cache
+    cache.read(device, 0, &mut buffer[..1]).unwrap();
+    let device_reads = reads.load(Ordering::SeqCst);
+    cache.read(device, size as u64 - 1, &mut buffer[..1]).unwrap();
+    cache.read(device, 0, &mut buffer[..1]).unwrap();
+    assert_eq!(reads.load(Ordering::SeqCst), device_reads);
+
+    cache.remove_device(device).unwrap();
+    assert!(data.lock().unwrap().iter().all(|byte| *byte == 1));
+    assert_eq!(ALLOCATED_PAGES.load(Ordering::SeqCst), pages_before);
+}
+
+#[test]
+fn read_only_devices_refuse_writes(){
+    let _lock = LOCK.lock().unwrap();
+    let mut cache = PageCache::new();
+    let device = cache.add_device(Box::new(MemoryDisk::new(b"short module")));
+    let mut buffer = [0; 20];
+    cache.read(device, 0, &mut buffer).unwrap();
+    assert_eq!(&buffer[..12], b"short module");
+    assert_eq!(cache.write(device, 0, b"x").err(), Some(Error::ReadOnly));
+    assert_eq!(cache.read(7, 0, &mut buffer).err(), Some(Error::NoSuchDevice));
+}
@@ -0,0 +1,37 @@
+use host_tests::fs::vfs::PathBuf;
+
+fn join(base: &str, path: &str) -> String{
+    PathBuf::from(base).join(path).to_string()
+}
+
+#[test]
+fn relative_paths_start_from_the_base(){
+    assert_eq!(join("/home", "user/file"), "/home/user/file");
+    assert_eq!(join("/", "file"), "/file");
+}
+
+#[test]
+fn absolute_paths_replace_the_base(){
+    assert_eq!(join("/home/user", "/etc/passwd"), "/etc/passwd");
+}
+
+#[test]
+fn dots_and_empty_components_are_removed(){
+    assert_eq!(join("/a", "./b//c/."), "/a/b/c");
+    assert_eq!(join("/a/b", "../c"), "/a/c");
+    assert_eq!(join("/a/b", "../../.."), "/");
+}
+
+#[test]
+fn parent_of_the_root_is_the_root(){
+    assert_eq!(PathBuf::from("/a/b").parent().to_string(), "/a");
+    assert_eq!(PathBuf::from("/").parent().to_string(), "/");
+}
+
+#[test]
+fn from_str_is_absolute(){
+    let path = PathBuf::from("a/../b/c");
+    assert_eq!(path.len(), 2);
+    assert_eq!(path, PathBuf::from("/b/c"));
+    assert!(PathBuf::from("").is_empty());
+}
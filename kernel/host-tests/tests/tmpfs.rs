@@ -0,0 +1,49 @@
+use host_tests::fs::{tmpfs, vfs::{Error, MountPoint, PathBuf}};
+
+const MIB: usize = 1024 * 1024;
+
+fn with_files(names: &[&str]) -> MountPoint{
+    let mut filesystem = tmpfs::new_tmpfs();
+    for name in names{
+        filesystem.create(PathBuf::new(), name.to_string(), false).unwrap();
+    }
+    filesystem
+}
+
+#[test]
+fn writes_past_the_end_leave_zeroes(){
+    let mut filesystem = with_files(&["file"]);
+    let file = PathBuf::from("file");
+    assert_eq!(filesystem.write(file.clone(), 4, b"data").unwrap(), 4);
+    assert_eq!(&*filesystem.read(file.clone(), 0, 8).unwrap(), b"\0\0\0\0data");
+    filesystem.truncate(file.clone(), 2).unwrap();
+    assert_eq!(filesystem.get_size(file).unwrap(), 2);
+}
+
+#[test]
+fn files_are_limited_in_size(){
+    let mut filesystem = with_files(&["file"]);
+    let file = PathBuf::from("file");
+    assert!(matches!(filesystem.write(file.clone(), MIB, b"x"), Err(Error::FileTooBig)));
+    assert!(matches!(filesystem.truncate(file.clone(), MIB + 1), Err(Error::FileTooBig)));
+    assert!(matches!(filesystem.write(file.clone(), usize::MAX, b"x"), Err(Error::FileTooBig)));
+    filesystem.truncate(file.clone(), MIB).unwrap();
+    assert_eq!(filesystem.get_size(file).unwrap(), MIB);
+}
+
+#[test]
+fn the_file_system_is_limited_in_size(){
+    let names = ["0", "1", "2", "3", "4"];
+    let mut filesystem = with_files(&names);
+    for name in &names[..4]{
+        filesystem.truncate(PathBuf::from(*name), MIB).unwrap();
+    }
+    let last = PathBuf::from("4");
+    assert!(matches!(filesystem.write(last.clone(), 0, b"x"), Err(Error::NoSpace)));
+    // Emptied and removed files give their space back
+    filesystem.truncate(PathBuf::from("0"), 0).unwrap();
+    filesystem.write(last.clone(), 0, b"x").unwrap();
+    assert!(matches!(filesystem.truncate(PathBuf::from("0"), MIB), Err(Error::NoSpace)));
+    filesystem.unlink(PathBuf::new(), "1").unwrap();
+    filesystem.truncate(PathBuf::from("0"), MIB).unwrap();
+}
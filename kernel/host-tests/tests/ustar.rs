@@ -0,0 +1,173 @@
+mod common;
+
+use common::{Entry, leak, read_all, tar_archive, ustar_fs};
+use host_tests::fs::{ustar::{self, ParseError}, vfs::{FileKind, PathBuf}};
+
+#[test]
+fn files_are_loaded(){
+    let filesystem = ustar_fs(tar_archive(&[
+        Entry::Folder("bin/"),
+        Entry::File("bin/init.elf", b"\x7fELF"),
+        Entry::File("etc/motd", b"hello"),
+    ]));
+    assert_eq!(read_all(&filesystem, "bin/init.elf"), b"\x7fELF");
+    // etc has no entry of its own
+    assert_eq!(read_all(&filesystem, "etc/motd"), b"hello");
+}
+
+#[test]
+fn metadata_comes_from_the_header(){
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_ustar();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_mode(0o4750);
+    header.set_uid(1000);
+    header.set_gid(100);
+    header.set_mtime(1_700_000_000);
+    header.set_size(0);
+    builder.append_data(&mut header, "file", &[][..]).unwrap();
+    let filesystem = ustar_fs(leak(builder.into_inner().unwrap()));
+    let metadata = filesystem.get_metadata(PathBuf::from("file")).unwrap();
+    assert_eq!((metadata.mode, metadata.uid, metadata.gid, metadata.mtime), (0o4750, 1000, 100, 1_700_000_000));
+}
+
+#[test]
+fn reads_are_clamped_to_the_file(){
+    let filesystem = ustar_fs(tar_archive(&[Entry::File("file", b"0123456789")]));
+    let file = PathBuf::from("file");
+    assert_eq!(&*filesystem.read(file.clone(), 4, 3).unwrap(), b"456");
+    assert_eq!(&*filesystem.read(file.clone(), 8, usize::MAX).unwrap(), b"89");
+    assert!(filesystem.read(file, 20, 5).unwrap().is_empty());
+}
+
+#[test]
+fn folders_are_listed_in_name_order(){
+    let filesystem = ustar_fs(tar_archive(&[
+        Entry::Folder("bin/"),
+        Entry::File("bin/init.elf", b"\x7fELF"),
+        Entry::File("motd", b"hello"),
+        Entry::File("bin/ls.elf", b"\x7fELF"),
+    ]));
+    let entries = filesystem.read_dir(PathBuf::new()).unwrap();
+    let names: Vec<(&str, bool)> = entries.iter().map(|entry| (entry.get_name(), entry.is_folder())).collect();
+    assert_eq!(names, [("bin", true), ("motd", false)]);
+    let bin = filesystem.read_dir(PathBuf::from("bin")).unwrap();
+    assert_eq!(bin.iter().map(|entry| entry.get_name()).collect::<Vec<_>>(), ["init.elf", "ls.elf"]);
+    assert!(filesystem.read_dir(PathBuf::from("motd")).is_err());
+}
+
+#[test]
+fn symlinks_keep_their_target(){
+    let filesystem = ustar_fs(tar_archive(&[Entry::File("file", b"x"), Entry::SymLink("link", "../file")]));
+    let link = filesystem.get_root().find(PathBuf::from("link")).unwrap();
+    assert_eq!(link.get_symlink_target(), Some("../file"));
+}
+
+#[test]
+fn symlinks_are_listed_with_their_kind(){
+    let filesystem = ustar_fs(tar_archive(&[Entry::Folder("bin/"), Entry::File("file", b"x"), Entry::SymLink("link", "file")]));
+    let entries = filesystem.read_dir(PathBuf::new()).unwrap();
+    let kinds: Vec<(&str, FileKind)> = entries.iter().map(|entry| (entry.get_name(), entry.get_kind())).collect();
+    assert_eq!(kinds, [("bin", FileKind::Folder), ("file", FileKind::RegularFile), ("link", FileKind::SymLink)]);
+}
+
+#[test]
+fn hard_links_share_the_data(){
+    let filesystem = ustar_fs(tar_archive(&[
+        Entry::Folder("data/"),
+        Entry::HardLink("early", "data/file"),
+        Entry::File("data/file", b"shared"),
+        Entry::HardLink("late", "data/file"),
+    ]));
+    assert_eq!(read_all(&filesystem, "/early"), b"shared");
+    assert_eq!(read_all(&filesystem, "/late"), b"shared");
+}
+
+#[test]
+fn hard_link_targets_can_go_through_symlinked_folders(){
+    let filesystem = ustar_fs(tar_archive(&[
+        Entry::Folder("real/"),
+        Entry::SymLink("alias", "real"),
+        Entry::File("real/file", b"hello"),
+        Entry::Folder("other/"),
+        Entry::HardLink("other/link", "alias/file"),
+    ]));
+    assert_eq!(read_all(&filesystem, "/other/link"), b"hello");
+}
+
+#[test]
+fn long_gnu_names_are_used(){
+    let name = format!("{}/file", "folder".repeat(30));
+    let filesystem = ustar_fs(tar_archive(&[Entry::Folder(&format!("{}/", "folder".repeat(30))), Entry::File(&name, b"long")]));
+    assert_eq!(read_all(&filesystem, &name), b"long");
+}
+
+#[test]
+fn pax_attributes_replace_the_header_fields(){
+    let name = format!("{}/file", "pax".repeat(60));
+    let mut builder = tar::Builder::new(Vec::new());
+    // The folder has a GNU long name record
+    let mut folder = tar::Header::new_gnu();
+    folder.set_entry_type(tar::EntryType::Directory);
+    folder.set_size(0);
+    builder.append_data(&mut folder, format!("{}/", "pax".repeat(60)), &[][..]).unwrap();
+    builder.append_pax_extensions([("path", name.as_bytes()), ("uid", b"4242".as_slice())]).unwrap();
+    let mut header = tar::Header::new_ustar();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_path("short").unwrap();
+    header.set_size(3);
+    header.set_cksum();
+    builder.append(&header, &b"pax"[..]).unwrap();
+    let filesystem = ustar_fs(leak(builder.into_inner().unwrap()));
+    assert_eq!(read_all(&filesystem, &name), b"pax");
+    assert_eq!(filesystem.get_metadata(PathBuf::from(name.as_str())).unwrap().uid, 4242);
+    assert!(filesystem.get_root().find(PathBuf::from("short")).is_err());
+}
+
+#[test]
+fn ustar_prefix_is_prepended_to_the_name(){
+    let parent = "a".repeat(60);
+    let folder = format!("{parent}/{}", "b".repeat(60));
+    let name = format!("{folder}/file");
+    let mut builder = tar::Builder::new(Vec::new());
+    for (path, entry_type, data) in [(&parent, tar::EntryType::Directory, &b""[..]), (&folder, tar::EntryType::Directory, &b""[..]), (&name, tar::EntryType::Regular, &b"prefix"[..])]{
+        let mut header = tar::Header::new_ustar();
+        header.set_entry_type(entry_type);
+        header.set_path(path).unwrap();
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        builder.append(&header, data).unwrap();
+    }
+    let data = leak(builder.into_inner().unwrap());
+    // The name field only holds the end of the path
+    assert_eq!(&data[1024..1029], b"file\0");
+    let filesystem = ustar_fs(data);
+    assert_eq!(read_all(&filesystem, &name), b"prefix");
+}
+
+#[test]
+fn hard_links_to_missing_files_are_errors(){
+    let data = tar_archive(&[Entry::HardLink("link", "missing")]);
+    let result = ustar::headers_to_fs(ustar::parse_file(data).unwrap(), data);
+    assert!(matches!(result, Err(ParseError::MissingLinkTarget { .. })));
+}
+
+#[test]
+fn bad_checksums_are_detected(){
+    let mut data = tar_archive(&[Entry::File("file", b"data")]).to_vec();
+    data[0] ^= 1;
+    assert!(matches!(ustar::parse_file(&data), Err(ParseError::BadChecksum { offset: 0 })));
+}
+
+#[test]
+fn truncated_archives_are_detected(){
+    let data = tar_archive(&[Entry::File("file", &[1; 1000])]);
+    assert!(matches!(ustar::parse_file(&data[..1024]), Err(ParseError::Truncated { .. })));
+}
+
+#[test]
+fn files_inside_files_are_errors(){
+    let data = tar_archive(&[Entry::File("file", b"x"), Entry::File("file/inner", b"y")]);
+    let result = ustar::headers_to_fs(ustar::parse_file(data).unwrap(), data);
+    assert!(matches!(result, Err(ParseError::NotAFolder { .. })));
+}
@@ -23,7 +23,9 @@ pub extern "C" fn init_alloc(heap_virtual_addr_start: usize, heap_end: usize){
             if phys_addr == 0{
                 panic!("Failed to alloc pages for kernel heap");
             }
-            map_page_kernel(phys_addr, virt_addr, PTE_PRESENT | PTE_READ_WRITE);
+            if map_page_kernel(phys_addr, virt_addr, PTE_PRESENT | PTE_READ_WRITE) != 0{
+                panic!("Failed to map the kernel heap");
+            }
         }
         virt_addr += page_size;
     }
@@ -1,15 +1,12 @@
 use x86_64::registers::model_specific::Msr;
 
-use crate::{
-    PTE_PRESENT, PTE_READ_WRITE, alloc_page_phys_addr, keyboard, map_page_kernel, rsdt::MADT,
-};
+use crate::{keyboard, percpu, phys_addr_to_limine_virtual_addr, rsdt::MADT};
 pub mod timer;
 
 const IA32_APIC_BASE_MSR: u32 = 0x1B;
 const IA32_APIC_BASE_MSR_BSP: u64 = 0x100;
 const IA32_APIC_BASE_MSR_ENABLE: u64 = 0x800;
 
-static mut base_addr: u64 = 0;
 static mut io_apic_addr: *const u8 = core::ptr::null();
 
 pub fn has_local_apic() -> bool {
@@ -43,41 +40,29 @@ pub unsafe fn set_APIC_BASE(phys_addr: u64, is_BSP: bool) {
     }
 }
 
-pub unsafe fn alloc_mem() {
-    if unsafe { base_addr } == 0 {
-        let page = unsafe { alloc_page_phys_addr(1) };
-        let addr = page.addr();
-        unsafe {
-            map_page_kernel(addr, addr, PTE_PRESENT | PTE_READ_WRITE);
-            base_addr = addr as u64;
-        }
-    }
-}
-
 pub fn setup_apic() {
     crate::pic::PIC_remap(0x20, 0x28); // Remap the PIC and mask all its interrupts to deactivate it
     if !has_local_apic() {
         panic!("APIC not supported");
     }
-    unsafe {
-        base_addr = get_APIC_BASE();
-        map_page_kernel(
-            base_addr as usize,
-            base_addr as usize,
-            PTE_PRESENT | PTE_READ_WRITE,
-        );
-        set_APIC_BASE(base_addr, is_bsp());
-    };
-    if unsafe { base_addr } != get_APIC_BASE() {
+    // The registers are accessed through the HHDM so that they stay mapped in every address space
+    let percpu = percpu::get();
+    percpu.lapic_base = get_APIC_BASE();
+    unsafe { set_APIC_BASE(percpu.lapic_base, is_bsp()) };
+    if percpu.lapic_base != get_APIC_BASE() {
         panic!("Failed to setup APIC base address");
     }
 
     write_lapic(0xF0, read_lapic(0xF0) | 0x100);
 }
 
+fn lapic_registers() -> *mut u32 {
+    unsafe { phys_addr_to_limine_virtual_addr(percpu::get().lapic_base as usize) as *mut u32 }
+}
+
 pub fn read_lapic(reg: u32) -> u32 {
     unsafe {
-        (base_addr as *const u32)
+        lapic_registers()
             .byte_offset(reg.try_into().unwrap())
             .read_volatile()
     }
@@ -85,10 +70,10 @@ pub fn read_lapic(reg: u32) -> u32 {
 
 pub fn write_lapic(reg: u32, value: u32) {
     unsafe {
-        (base_addr as *mut u32)
+        lapic_registers()
             .byte_offset(reg.try_into().unwrap())
             .write_volatile(value);
-        (base_addr as *mut u32).byte_offset(0x20).read_volatile(); // read to ensure we wait long enough for the write to complete
+        lapic_registers().byte_offset(0x20).read_volatile(); // read to ensure we wait long enough for the write to complete
     }
 }
 
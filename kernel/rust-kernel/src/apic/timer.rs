@@ -1,3 +1,5 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
 use crate::{apic::{read_lapic, write_lapic}, pit};
 
 const APIC_REGISTER_TIMER_DIV: u32 = 0x3E0;
@@ -6,6 +8,10 @@ const APIC_REGISTER_TIMER_CURRCNT: u32 = 0x390;
 const APIC_REGISTER_LVT_TIMER: u32 = 0x320;
 const APIC_LVT_INT_MASKED: u32 = 1<<16;
 const APIC_TIMER_PERIODIC: u32 = 0x20000;
+pub const TICK_MS: u64 = 10;
+
+// Measured once by the bootstrap processor, the other cores reuse it
+static TICKS_PER_PERIOD: AtomicU32 = AtomicU32::new(0);
 
 // Setup to apic timer to tick every 10 ms
 pub fn setup_apic_timer(){
@@ -13,7 +19,7 @@ pub fn setup_apic_timer(){
     write_lapic(APIC_REGISTER_TIMER_DIV, 0x3);
 
     // Sleep for 10ms using pit to know how many tick the apic timer did in 10ms
-    pit::prepare_sleep(10);
+    pit::prepare_sleep(TICK_MS);
 
     write_lapic(APIC_REGISTER_TIMER_INITCNT, 0xFFFF_FFFF);
     
@@ -23,10 +29,14 @@ pub fn setup_apic_timer(){
     write_lapic(APIC_REGISTER_LVT_TIMER, APIC_LVT_INT_MASKED);
 
     let tickIn10ms = 0xFFFF_FFFF - read_lapic(APIC_REGISTER_TIMER_CURRCNT);
+    TICKS_PER_PERIOD.store(tickIn10ms, Ordering::Relaxed);
 
-    // Start timer as periodic
+    start_apic_timer();
+}
 
+// Start timer as periodic, setup_apic_timer must have been called on the bootstrap processor
+pub fn start_apic_timer(){
     write_lapic(APIC_REGISTER_LVT_TIMER, 50 | APIC_TIMER_PERIODIC);
     write_lapic(APIC_REGISTER_TIMER_DIV, 0x3);
-    write_lapic(APIC_REGISTER_TIMER_INITCNT, tickIn10ms);
+    write_lapic(APIC_REGISTER_TIMER_INITCNT, TICKS_PER_PERIOD.load(Ordering::Relaxed));
 }
\ No newline at end of file
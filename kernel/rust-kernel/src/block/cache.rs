@@ -0,0 +1,218 @@
+use alloc::{boxed::Box, collections::BTreeMap};
+use spin::Mutex;
+
+use crate::{alloc_page_phys_addr, free_pages, phys_addr_to_limine_virtual_addr};
+use super::{BlockDevice, Error, SECTOR_SIZE};
+
+// The devices are cached by blocks of one page
+pub const BLOCK_SIZE: usize = 4096;
+const SECTORS_PER_BLOCK: u64 = (BLOCK_SIZE / SECTOR_SIZE) as u64;
+
+// 4 MiB, the least recently used block is dropped to make room for a new one
+const MAX_CACHED_BLOCKS: usize = 1024;
+
+pub type DeviceId = usize;
+
+// Content of a block kept in a physical page, the kernel heap is too small for the cache
+struct CachedBlock{
+    ptr: *mut u8,
+    dirty: bool, // Written since it was read from the device
+    last_used: u64,
+}
+
+// The page is only accessed with the cache locked
+unsafe impl Send for CachedBlock {}
+
+impl CachedBlock{
+    fn new() -> Result<Self, Error>{
+        let phys_addr = unsafe { alloc_page_phys_addr(1) } as usize;
+        if phys_addr == 0{
+            return Err(Error::OutOfMemory);
+        }
+        let ptr = unsafe { phys_addr_to_limine_virtual_addr(phys_addr) } as *mut u8;
+        Ok(CachedBlock { ptr, dirty: false, last_used: 0 })
+    }
+
+    fn as_slice(&self) -> &[u8]{
+        unsafe { core::slice::from_raw_parts(self.ptr, BLOCK_SIZE) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8]{
+        unsafe { core::slice::from_raw_parts_mut(self.ptr, BLOCK_SIZE) }
+    }
+}
+
+impl Drop for CachedBlock{
+    fn drop(&mut self){
+        unsafe { free_pages(self.ptr as *mut core::ffi::c_void, 1) };
+    }
+}
+
+// Sectors of the block that are on the device, the last block of a device can be partial
+fn get_block_sectors(device: &dyn BlockDevice, block: u64) -> Result<(u64, usize), Error>{
+    let first_sector = block.checked_mul(SECTORS_PER_BLOCK).ok_or(Error::OutOfRange)?;
+    let sector_count = device.get_sector_count();
+    if first_sector >= sector_count{
+        return Err(Error::OutOfRange);
+    }
+    Ok((first_sector, (sector_count - first_sector).min(SECTORS_PER_BLOCK) as usize))
+}
+
+fn write_back(device: &mut dyn BlockDevice, block: u64, cached: &mut CachedBlock) -> Result<(), Error>{
+    if cached.dirty{
+        let (first_sector, sectors) = get_block_sectors(device, block)?;
+        device.write_sectors(first_sector, &cached.as_slice()[..sectors * SECTOR_SIZE])?;
+        cached.dirty = false;
+    }
+    Ok(())
+}
+
+// Blocks of the registered devices, indexed by (device, block). The writes stay in the
+// cache until the block is evicted or the device is synced
+#[derive(Default)]
+pub struct PageCache{
+    devices: BTreeMap<DeviceId, Box<dyn BlockDevice>>,
+    blocks: BTreeMap<(DeviceId, u64), CachedBlock>,
+    next_device_id: DeviceId,
+    clock: u64, // Incremented on each access, orders the blocks by last use
+}
+
+static PAGE_CACHE: Mutex<PageCache> = Mutex::new(PageCache::new());
+
+impl PageCache{
+    pub const fn new() -> Self{
+        PageCache { devices: BTreeMap::new(), blocks: BTreeMap::new(), next_device_id: 0, clock: 0 }
+    }
+
+    pub fn add_device(&mut self, device: Box<dyn BlockDevice>) -> DeviceId{
+        let id = self.next_device_id;
+        self.next_device_id += 1;
+        self.devices.insert(id, device);
+        id
+    }
+
+    // The dirty blocks are written before the device is given back
+    pub fn remove_device(&mut self, id: DeviceId) -> Result<Box<dyn BlockDevice>, Error>{
+        self.sync(id)?;
+        self.blocks.retain(|(device, _), _| *device != id);
+        self.devices.remove(&id).ok_or(Error::NoSuchDevice)
+    }
+
+    pub fn get_device_size(&self, id: DeviceId) -> Result<u64, Error>{
+        Ok(self.devices.get(&id).ok_or(Error::NoSuchDevice)?.get_size())
+    }
+
+    // Drop the least recently used block, it is written first if needed
+    fn evict(&mut self) -> Result<(), Error>{
+        let Some((&key, _)) = self.blocks.iter().min_by_key(|(_, cached)| cached.last_used) else{
+            return Ok(());
+        };
+        let device = self.devices.get_mut(&key.0).ok_or(Error::NoSuchDevice)?;
+        write_back(device.as_mut(), key.1, self.blocks.get_mut(&key).unwrap())?;
+        self.blocks.remove(&key);
+        Ok(())
+    }
+
+    // The block is read from the device if it isn't cached
+    fn get_block(&mut self, id: DeviceId, block: u64) -> Result<&mut CachedBlock, Error>{
+        self.clock += 1;
+        if !self.blocks.contains_key(&(id, block)){
+            let device = self.devices.get(&id).ok_or(Error::NoSuchDevice)?;
+            let (first_sector, sectors) = get_block_sectors(device.as_ref(), block)?;
+            if self.blocks.len() >= MAX_CACHED_BLOCKS{
+                self.evict()?;
+            }
+            let mut cached = CachedBlock::new()?;
+            let buffer = cached.as_mut_slice();
+            buffer[sectors * SECTOR_SIZE..].fill(0);
+            let device = self.devices.get_mut(&id).ok_or(Error::NoSuchDevice)?;
+            device.read_sectors(first_sector, &mut buffer[..sectors * SECTOR_SIZE])?;
+            self.blocks.insert((id, block), cached);
+        }
+        let cached = self.blocks.get_mut(&(id, block)).unwrap();
+        cached.last_used = self.clock;
+        Ok(cached)
+    }
+
+    // Read from pos, less bytes are read at the end of the device
+    pub fn read(&mut self, id: DeviceId, pos: u64, buffer: &mut [u8]) -> Result<usize, Error>{
+        let size = self.get_device_size(id)?;
+        let len = (size.saturating_sub(pos) as usize).min(buffer.len());
+        let mut done = 0;
+        while done < len{
+            let current = pos + done as u64;
+            let offset = (current % BLOCK_SIZE as u64) as usize;
+            let amount = (BLOCK_SIZE - offset).min(len - done);
+            let cached = self.get_block(id, current / BLOCK_SIZE as u64)?;
+            buffer[done..done + amount].copy_from_slice(&cached.as_slice()[offset..offset + amount]);
+            done += amount;
+        }
+        Ok(done)
+    }
+
+    // Write at pos, the device doesn't grow so less bytes are written at its end
+    pub fn write(&mut self, id: DeviceId, pos: u64, data: &[u8]) -> Result<usize, Error>{
+        let device = self.devices.get(&id).ok_or(Error::NoSuchDevice)?;
+        if device.is_read_only(){
+            return Err(Error::ReadOnly);
+        }
+        let len = (device.get_size().saturating_sub(pos) as usize).min(data.len());
+        let mut done = 0;
+        while done < len{
+            let current = pos + done as u64;
+            let offset = (current % BLOCK_SIZE as u64) as usize;
+            let amount = (BLOCK_SIZE - offset).min(len - done);
+            // The block is read first, a write can cover only part of it
+            let cached = self.get_block(id, current / BLOCK_SIZE as u64)?;
+            cached.as_mut_slice()[offset..offset + amount].copy_from_slice(&data[done..done + amount]);
+            cached.dirty = true;
+            done += amount;
+        }
+        Ok(done)
+    }
+
+    // Run f with the content of a block, without copying it
+    pub fn with_block<T>(&mut self, id: DeviceId, block: u64, f: impl FnOnce(&[u8]) -> T) -> Result<T, Error>{
+        Ok(f(self.get_block(id, block)?.as_slice()))
+    }
+
+    // Write the dirty blocks of the device
+    pub fn sync(&mut self, id: DeviceId) -> Result<(), Error>{
+        let device = self.devices.get_mut(&id).ok_or(Error::NoSuchDevice)?;
+        for ((_, block), cached) in self.blocks.range_mut((id, 0)..=(id, u64::MAX)){
+            write_back(device.as_mut(), *block, cached)?;
+        }
+        Ok(())
+    }
+}
+
+// The functions below use the page cache shared by the whole kernel. f must not use the cache,
+// it is locked while f runs
+
+pub fn register_device(device: Box<dyn BlockDevice>) -> DeviceId{
+    PAGE_CACHE.lock().add_device(device)
+}
+
+pub fn unregister_device(id: DeviceId) -> Result<Box<dyn BlockDevice>, Error>{
+    PAGE_CACHE.lock().remove_device(id)
+}
+
+pub fn get_device_size(id: DeviceId) -> Result<u64, Error>{
+    PAGE_CACHE.lock().get_device_size(id)
+}
+
+pub fn read(id: DeviceId, pos: u64, buffer: &mut [u8]) -> Result<usize, Error>{
+    PAGE_CACHE.lock().read(id, pos, buffer)
+}
+
+pub fn write(id: DeviceId, pos: u64, data: &[u8]) -> Result<usize, Error>{
+    PAGE_CACHE.lock().write(id, pos, data)
+}
+
+pub fn with_block<T>(id: DeviceId, block: u64, f: impl FnOnce(&[u8]) -> T) -> Result<T, Error>{
+    PAGE_CACHE.lock().with_block(id, block, f)
+}
+
+pub fn sync(id: DeviceId) -> Result<(), Error>{
+    PAGE_CACHE.lock().sync(id)
+}
@@ -0,0 +1,39 @@
+use super::{BlockDevice, Error, SECTOR_SIZE};
+
+// Read-only device over data already in memory, for example a boot module.
+// A partial last sector reads as zeros after the end of the data
+pub struct MemoryDisk{
+    data: &'static [u8],
+}
+
+impl MemoryDisk{
+    pub fn new(data: &'static [u8]) -> Self{
+        MemoryDisk { data }
+    }
+}
+
+impl BlockDevice for MemoryDisk{
+    fn get_sector_count(&self) -> u64{
+        self.data.len().div_ceil(SECTOR_SIZE) as u64
+    }
+
+    fn read_sectors(&mut self, sector: u64, buffer: &mut [u8]) -> Result<(), Error>{
+        let start = (sector as usize).checked_mul(SECTOR_SIZE).ok_or(Error::OutOfRange)?;
+        let end = start.checked_add(buffer.len()).ok_or(Error::OutOfRange)?;
+        if end > self.get_sector_count() as usize * SECTOR_SIZE{
+            return Err(Error::OutOfRange);
+        }
+        let available = &self.data[start.min(self.data.len())..end.min(self.data.len())];
+        buffer[..available.len()].copy_from_slice(available);
+        buffer[available.len()..].fill(0);
+        Ok(())
+    }
+
+    fn write_sectors(&mut self, _sector: u64, _data: &[u8]) -> Result<(), Error>{
+        Err(Error::ReadOnly)
+    }
+
+    fn is_read_only(&self) -> bool{
+        true
+    }
+}
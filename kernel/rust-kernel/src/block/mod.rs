@@ -0,0 +1,42 @@
+use crate::fs::vfs;
+
+pub mod cache;
+pub mod memory_disk;
+
+// Unit read and written by the devices
+pub const SECTOR_SIZE: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error{
+    OutOfRange, // The sectors are after the end of the device
+    ReadOnly,
+    Io, // The device reported a failure
+    OutOfMemory, // No page left for the cache
+    NoSuchDevice,
+}
+
+// A disk read and written by whole sectors. The buffers given to the device
+// hold a whole number of sectors, starting at sector
+pub trait BlockDevice: Send{
+    fn get_sector_count(&self) -> u64;
+    fn read_sectors(&mut self, sector: u64, buffer: &mut [u8]) -> Result<(), Error>;
+    fn write_sectors(&mut self, sector: u64, data: &[u8]) -> Result<(), Error>;
+
+    fn is_read_only(&self) -> bool{
+        false
+    }
+
+    // Size in bytes
+    fn get_size(&self) -> u64{
+        self.get_sector_count() * SECTOR_SIZE as u64
+    }
+}
+
+impl From<Error> for vfs::Error{
+    fn from(error: Error) -> Self{
+        match error{
+            Error::ReadOnly => vfs::Error::ReadOnly,
+            Error::OutOfRange | Error::Io | Error::OutOfMemory | Error::NoSuchDevice => vfs::Error::Io,
+        }
+    }
+}
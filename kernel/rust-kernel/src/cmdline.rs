@@ -0,0 +1,133 @@
+use alloc::{collections::BTreeMap, string::{String, ToString}, vec::Vec};
+use spin::Mutex;
+
+// Started when the command line has no init=, relative to the root file system
+const DEFAULT_INIT: &str = "init.elf";
+
+// Messages more verbose than the loglevel= option aren't printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel{
+    Error,
+    Warning,
+    Info,
+    Debug,
+}
+
+// Where the kernel messages are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Console{
+    Screen,
+    Serial, // COM1
+}
+
+#[derive(Debug)]
+pub enum ParseError{
+    InvalidValue { key: String, value: String },
+}
+
+// Options given to the kernel by the cmdline of limine.conf, for example
+// "init=/bin/sh loglevel=debug console=serial smp=off"
+pub struct Config{
+    init: Option<String>,
+    loglevel: LogLevel,
+    console: Console,
+    smp: bool,
+    others: BTreeMap<String, String>, // Options that the kernel doesn't know, "key" alone has an empty value
+}
+
+static CONFIG: Mutex<Config> = Mutex::new(Config::new());
+
+impl Config{
+    pub const fn new() -> Self{
+        Config { init: None, loglevel: LogLevel::Info, console: Console::Screen, smp: true, others: BTreeMap::new() }
+    }
+
+    // The options are separated by spaces, the invalid ones are skipped and returned
+    pub fn parse(cmdline: &str) -> (Self, Vec<ParseError>){
+        let mut config = Config::new();
+        let mut errors = Vec::new();
+        for option in cmdline.split_whitespace(){
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            if let Err(error) = config.set(key, value){
+                errors.push(error);
+            }
+        }
+        (config, errors)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), ParseError>{
+        let invalid_value = || ParseError::InvalidValue { key: key.to_string(), value: value.to_string() };
+        match key{
+            "init" => {
+                if value.is_empty(){
+                    return Err(invalid_value());
+                }
+                self.init = Some(value.to_string());
+            },
+            "loglevel" => {
+                self.loglevel = match value{
+                    "error" => LogLevel::Error,
+                    "warning" => LogLevel::Warning,
+                    "info" => LogLevel::Info,
+                    "debug" => LogLevel::Debug,
+                    _ => return Err(invalid_value()),
+                };
+            },
+            "console" => {
+                self.console = match value{
+                    "screen" => Console::Screen,
+                    "serial" => Console::Serial,
+                    _ => return Err(invalid_value()),
+                };
+            },
+            "smp" => {
+                self.smp = match value{
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(invalid_value()),
+                };
+            },
+            _ => {
+                self.others.insert(key.to_string(), value.to_string());
+            },
+        }
+        Ok(())
+    }
+}
+
+impl Default for Config{
+    fn default() -> Self{
+        Config::new()
+    }
+}
+
+// Parse the command line given by the bootloader, must be called before the options are used.
+// Returns the options that were ignored
+pub fn init(cmdline: &str) -> Vec<ParseError>{
+    let (config, errors) = Config::parse(cmdline);
+    *CONFIG.lock() = config;
+    errors
+}
+
+// Path of the first user process
+pub fn get_init() -> String{
+    CONFIG.lock().init.clone().unwrap_or_else(|| DEFAULT_INIT.to_string())
+}
+
+pub fn get_loglevel() -> LogLevel{
+    CONFIG.lock().loglevel
+}
+
+pub fn get_console() -> Console{
+    CONFIG.lock().console
+}
+
+// Whether the other cores are started
+pub fn is_smp_enabled() -> bool{
+    CONFIG.lock().smp
+}
+
+// Value of an option that isn't known by the kernel
+pub fn get(key: &str) -> Option<String>{
+    CONFIG.lock().others.get(key).cloned()
+}
@@ -0,0 +1,90 @@
+use super::{inflate::inflate, Error, Output};
+
+// gzip files (RFC 1952): a header, a deflate stream, then the CRC32 and the size of the content
+
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+const METHOD_DEFLATE: u8 = 8;
+
+const FLAG_HEADER_CRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+
+pub fn is_gzip(data: &[u8]) -> bool{
+    data.starts_with(&MAGIC)
+}
+
+const CRC_TABLE: [u32; 256] = make_crc_table();
+
+const fn make_crc_table() -> [u32; 256]{
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256{
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8{
+            crc = if crc & 1 != 0 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+pub fn crc32(data: &[u8]) -> u32{
+    !data.iter().fold(!0, |crc, byte| CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+// Members can be concatenated, zeros after the last one are ignored
+pub fn decompress(data: &[u8]) -> Result<Output, Error>{
+    let mut output = Output::new();
+    // The size of the last member is at the end, it is the size of the content if there is only one.
+    // It is only a hint, the allocation can still fail later.
+    if let Some(size) = data.last_chunk::<4>(){
+        let _ = output.reserve(u32::from_le_bytes(*size) as usize);
+    }
+    let mut pos = 0;
+    while is_gzip(&data[pos..]){
+        pos += decompress_member(&data[pos..], &mut output)?;
+    }
+    if pos == 0 || data[pos..].iter().any(|byte| *byte != 0){
+        return Err(Error::InvalidData("data after the gzip stream"));
+    }
+    Ok(output)
+}
+
+// Returns the size of the member
+fn decompress_member(data: &[u8], output: &mut Output) -> Result<usize, Error>{
+    let header = data.get(..10).ok_or(Error::Truncated)?;
+    if header[2] != METHOD_DEFLATE{
+        return Err(Error::InvalidData("unknown compression method"));
+    }
+    let flags = header[3];
+    let mut pos = 10;
+    if flags & FLAG_EXTRA != 0{
+        let len = data.get(pos..pos + 2).ok_or(Error::Truncated)?;
+        pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    // The name and the comment are null terminated
+    for flag in [FLAG_NAME, FLAG_COMMENT]{
+        if flags & flag != 0{
+            let len = data.get(pos..).and_then(|rest| rest.iter().position(|c| *c == 0)).ok_or(Error::Truncated)?;
+            pos += len + 1;
+        }
+    }
+    if flags & FLAG_HEADER_CRC != 0{
+        pos += 2;
+    }
+
+    let start = output.len();
+    pos += inflate(data.get(pos..).ok_or(Error::Truncated)?, output)?;
+    let trailer = data.get(pos..pos + 8).ok_or(Error::Truncated)?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    // The size is modulo 2^32
+    if crc32(&output.as_slice()[start..]) != crc || (output.len() - start) as u32 != size{
+        return Err(Error::ChecksumMismatch);
+    }
+    Ok(pos + 8)
+}
@@ -0,0 +1,208 @@
+use alloc::{vec, vec::Vec};
+
+use super::{Error, Output};
+
+// Decoder for the deflate format (RFC 1951) used by gzip.
+// The Huffman codes are decoded one bit at a time like zlib's puff, which is simple but slower than zlib.
+
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// Order of the lengths of the code used to compress the lengths of a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+// The bits are read from the least significant bit of each byte
+struct BitReader<'a>{
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitReader<'_>{
+    fn bits(&mut self, n: u32) -> Result<u32, Error>{
+        while self.count < n{
+            let byte = *self.data.get(self.pos).ok_or(Error::Truncated)?;
+            self.pos += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    // Drop the rest of the current byte, bytes are only loaded when needed so nothing else is buffered
+    fn align(&mut self){
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+// Canonical Huffman code given by the length of the code of each symbol
+struct Huffman{
+    counts: [u16; MAX_BITS + 1], // Number of codes of each length
+    symbols: Vec<u16>, // Symbols ordered by code
+}
+
+impl Huffman{
+    // A length of 0 means the symbol isn't used. Incomplete codes are allowed, a missing code fails when decoded
+    fn new(lengths: &[u8]) -> Result<Self, Error>{
+        let mut counts = [0u16; MAX_BITS + 1];
+        for len in lengths.iter(){
+            counts[*len as usize] += 1;
+        }
+        let mut left: i32 = 1;
+        for count in counts.iter().skip(1){
+            left = (left << 1) - *count as i32;
+            if left < 0{
+                return Err(Error::InvalidData("over-subscribed Huffman code"));
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS{
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, len) in lengths.iter().enumerate(){
+            if *len != 0{
+                symbols[offsets[*len as usize] as usize] = symbol as u16;
+                offsets[*len as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, Error>{
+        // code is the bits read so far, first the first code of the current length
+        // and index the position of that code in symbols
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for len in 1..=MAX_BITS{
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count{
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(Error::InvalidData("invalid Huffman code"))
+    }
+}
+
+// Decompress the deflate stream at the start of data at the end of output,
+// returns the number of bytes of data it took
+pub fn inflate(data: &[u8], output: &mut Output) -> Result<usize, Error>{
+    let mut reader = BitReader { data, pos: 0, buffer: 0, count: 0 };
+    loop{
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)?{
+            0 => stored_block(&mut reader, output)?,
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                compressed_block(&mut reader, output, &literals, &distances)?;
+            },
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                compressed_block(&mut reader, output, &literals, &distances)?;
+            },
+            _ => return Err(Error::InvalidData("invalid block type")),
+        }
+        if last{
+            return Ok(reader.pos);
+        }
+    }
+}
+
+fn stored_block(reader: &mut BitReader, output: &mut Output) -> Result<(), Error>{
+    reader.align();
+    let header = reader.data.get(reader.pos..reader.pos + 4).ok_or(Error::Truncated)?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+    if len != !complement{
+        return Err(Error::InvalidData("invalid stored block length"));
+    }
+    reader.pos += 4;
+    let block = reader.data.get(reader.pos..reader.pos + len as usize).ok_or(Error::Truncated)?;
+    output.extend_from_slice(block)?;
+    reader.pos += block.len();
+    Ok(())
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), Error>{
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+// The lengths of the literal/length and distance codes are themselves Huffman coded
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), Error>{
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30{
+        return Err(Error::InvalidData("too many length or distance symbols"));
+    }
+    let mut code_lengths = [0u8; 19];
+    for symbol in CODE_LENGTH_ORDER[..code_length_count].iter(){
+        code_lengths[*symbol] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len(){
+        let symbol = code_lengths.decode(reader)?;
+        let (len, repeat) = match symbol{
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..i].last().ok_or(Error::InvalidData("repeat with no previous length"))?;
+                (previous, 3 + reader.bits(2)? as usize)
+            },
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len(){
+            return Err(Error::InvalidData("too many code lengths"));
+        }
+        lengths[i..i + repeat].fill(len);
+        i += repeat;
+    }
+    if lengths[256] == 0{
+        return Err(Error::InvalidData("missing end of block code"));
+    }
+    Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+}
+
+fn compressed_block(reader: &mut BitReader, output: &mut Output, literals: &Huffman, distances: &Huffman) -> Result<(), Error>{
+    loop{
+        let symbol = literals.decode(reader)?;
+        match symbol{
+            0..=255 => output.push(symbol as u8)?,
+            256 => return Ok(()),
+            _ => {
+                let index = symbol as usize - 257;
+                if index >= LENGTH_BASE.len(){
+                    return Err(Error::InvalidData("invalid length symbol"));
+                }
+                let len = LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = distances.decode(reader)? as usize;
+                if index >= DISTANCE_BASE.len(){
+                    return Err(Error::InvalidData("invalid distance symbol"));
+                }
+                let distance = DISTANCE_BASE[index] as usize + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                output.copy_match(distance, len)?;
+            },
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use super::{Error, Output};
+
+// LZ4 frames (lz4 command) and legacy frames (lz4 -l, used by Linux for initrds)
+
+const FRAME_MAGIC: u32 = 0x184d2204;
+const LEGACY_FRAME_MAGIC: u32 = 0x184c2102;
+// The low 4 bits of the magic of a skippable frame can be anything, these frames only hold metadata
+const SKIPPABLE_FRAME_MAGIC: u32 = 0x184d2a50;
+const SKIPPABLE_FRAME_MASK: u32 = 0xfffffff0;
+
+const LEGACY_BLOCK_SIZE: usize = 8 << 20;
+
+const FLAG_VERSION_MASK: u8 = 0xc0;
+const FLAG_VERSION_1: u8 = 0x40;
+const FLAG_BLOCK_CHECKSUM: u8 = 0x10;
+const FLAG_CONTENT_SIZE: u8 = 0x08;
+const FLAG_CONTENT_CHECKSUM: u8 = 0x04;
+const FLAG_DICTIONARY_ID: u8 = 0x01;
+
+const BLOCK_UNCOMPRESSED: u32 = 0x8000_0000;
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32, Error>{
+    let bytes = data.get(pos..pos + 4).ok_or(Error::Truncated)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub fn is_lz4(data: &[u8]) -> bool{
+    matches!(read_u32(data, 0), Ok(FRAME_MAGIC | LEGACY_FRAME_MAGIC))
+}
+
+// Frames can be concatenated, zeros after the last one are ignored
+pub fn decompress(data: &[u8]) -> Result<Output, Error>{
+    let mut output = Output::new();
+    let mut pos = 0;
+    while pos < data.len(){
+        let magic = read_u32(data, pos)?;
+        pos += 4;
+        pos = match magic{
+            FRAME_MAGIC => decompress_frame(data, pos, &mut output)?,
+            LEGACY_FRAME_MAGIC => decompress_legacy_frame(data, pos, &mut output)?,
+            _ if magic & SKIPPABLE_FRAME_MASK == SKIPPABLE_FRAME_MAGIC => {
+                let len = read_u32(data, pos)? as usize;
+                pos.checked_add(4 + len).ok_or(Error::Truncated)?
+            },
+            0 if data[pos..].iter().all(|byte| *byte == 0) => break,
+            _ => return Err(Error::InvalidData("unknown LZ4 frame magic")),
+        };
+    }
+    Ok(output)
+}
+
+// Returns the position after the frame
+fn decompress_frame(data: &[u8], mut pos: usize, output: &mut Output) -> Result<usize, Error>{
+    let descriptor_start = pos;
+    let descriptor = data.get(pos..pos + 2).ok_or(Error::Truncated)?;
+    let (flags, block_descriptor) = (descriptor[0], descriptor[1]);
+    if flags & FLAG_VERSION_MASK != FLAG_VERSION_1{
+        return Err(Error::InvalidData("unsupported LZ4 frame version"));
+    }
+    if flags & FLAG_DICTIONARY_ID != 0{
+        return Err(Error::InvalidData("LZ4 dictionaries are not supported"));
+    }
+    let max_block_size = match (block_descriptor >> 4) & 7{
+        4 => 64 << 10,
+        5 => 256 << 10,
+        6 => 1 << 20,
+        7 => 4 << 20,
+        _ => return Err(Error::InvalidData("invalid LZ4 block maximum size")),
+    };
+    pos += 2;
+    if flags & FLAG_CONTENT_SIZE != 0{
+        let size = data.get(pos..pos + 8).ok_or(Error::Truncated)?;
+        // Only a hint, the allocation can still fail later
+        let _ = output.reserve(u64::from_le_bytes(size.try_into().unwrap()) as usize);
+        pos += 8;
+    }
+    let header_checksum = *data.get(pos).ok_or(Error::Truncated)?;
+    if (xxh32(&data[descriptor_start..pos], 0) >> 8) as u8 != header_checksum{
+        return Err(Error::ChecksumMismatch);
+    }
+    pos += 1;
+
+    let start = output.len();
+    loop{
+        let size = read_u32(data, pos)?;
+        pos += 4;
+        // End mark
+        if size == 0{
+            break;
+        }
+        let block_size = (size & !BLOCK_UNCOMPRESSED) as usize;
+        if block_size > max_block_size{
+            return Err(Error::InvalidData("LZ4 block too big"));
+        }
+        let block = data.get(pos..pos + block_size).ok_or(Error::Truncated)?;
+        if size & BLOCK_UNCOMPRESSED != 0{
+            output.extend_from_slice(block)?;
+        }else{
+            decompress_block(block, output, max_block_size)?;
+        }
+        pos += block_size;
+        if flags & FLAG_BLOCK_CHECKSUM != 0{
+            if read_u32(data, pos)? != xxh32(block, 0){
+                return Err(Error::ChecksumMismatch);
+            }
+            pos += 4;
+        }
+    }
+    if flags & FLAG_CONTENT_CHECKSUM != 0{
+        if read_u32(data, pos)? != xxh32(&output.as_slice()[start..], 0){
+            return Err(Error::ChecksumMismatch);
+        }
+        pos += 4;
+    }
+    Ok(pos)
+}
+
+// Compressed blocks preceded by their size, until the end of the data or the next frame
+fn decompress_legacy_frame(data: &[u8], mut pos: usize, output: &mut Output) -> Result<usize, Error>{
+    while pos < data.len(){
+        let size = read_u32(data, pos)?;
+        if size == FRAME_MAGIC || size == LEGACY_FRAME_MAGIC || size == 0{
+            return Ok(pos);
+        }
+        pos += 4;
+        let block = data.get(pos..pos + size as usize).ok_or(Error::Truncated)?;
+        decompress_block(block, output, LEGACY_BLOCK_SIZE)?;
+        pos += block.len();
+    }
+    Ok(pos)
+}
+
+// Lengths of 15 continue in the next bytes, until one isn't 255
+fn read_length(block: &[u8], pos: &mut usize, mut len: usize) -> Result<usize, Error>{
+    if len == 15{
+        loop{
+            let byte = *block.get(*pos).ok_or(Error::Truncated)?;
+            *pos += 1;
+            len = len.checked_add(byte as usize).ok_or(Error::InvalidData("LZ4 length overflow"))?;
+            if byte != 255{
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+// A block is a list of sequences: a token with the lengths, literals, then the offset of a match.
+// The last sequence only has literals.
+fn decompress_block(block: &[u8], output: &mut Output, max_size: usize) -> Result<(), Error>{
+    let start = output.len();
+    let mut pos = 0;
+    loop{
+        let token = *block.get(pos).ok_or(Error::Truncated)?;
+        pos += 1;
+        let literals_len = read_length(block, &mut pos, (token >> 4) as usize)?;
+        let literals = pos.checked_add(literals_len).and_then(|end| block.get(pos..end)).ok_or(Error::Truncated)?;
+        output.extend_from_slice(literals)?;
+        pos += literals_len;
+        if pos == block.len(){
+            break;
+        }
+        let offset = block.get(pos..pos + 2).ok_or(Error::Truncated)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        pos += 2;
+        let match_len = read_length(block, &mut pos, (token & 0xf) as usize)? + 4;
+        if output.len() - start + match_len > max_size{
+            return Err(Error::InvalidData("LZ4 block too big"));
+        }
+        output.copy_match(offset, match_len)?;
+    }
+    if output.len() - start > max_size{
+        return Err(Error::InvalidData("LZ4 block too big"));
+    }
+    Ok(())
+}
+
+const PRIME1: u32 = 0x9e3779b1;
+const PRIME2: u32 = 0x85ebca77;
+const PRIME3: u32 = 0xc2b2ae3d;
+const PRIME4: u32 = 0x27d4eb2f;
+const PRIME5: u32 = 0x165667b1;
+
+fn xxh32_round(acc: u32, input: u32) -> u32{
+    acc.wrapping_add(input.wrapping_mul(PRIME2)).rotate_left(13).wrapping_mul(PRIME1)
+}
+
+// Checksum used by the LZ4 frames
+fn xxh32(data: &[u8], seed: u32) -> u32{
+    let read = |pos: usize| u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
+    let mut pos = 0;
+    let mut hash = if data.len() >= 16{
+        let mut lanes = [seed.wrapping_add(PRIME1).wrapping_add(PRIME2), seed.wrapping_add(PRIME2), seed, seed.wrapping_sub(PRIME1)];
+        while pos + 16 <= data.len(){
+            for lane in lanes.iter_mut(){
+                *lane = xxh32_round(*lane, read(pos));
+                pos += 4;
+            }
+        }
+        lanes[0].rotate_left(1).wrapping_add(lanes[1].rotate_left(7)).wrapping_add(lanes[2].rotate_left(12)).wrapping_add(lanes[3].rotate_left(18))
+    }else{
+        seed.wrapping_add(PRIME5)
+    };
+    hash = hash.wrapping_add(data.len() as u32);
+    while pos + 4 <= data.len(){
+        hash = hash.wrapping_add(read(pos).wrapping_mul(PRIME3)).rotate_left(17).wrapping_mul(PRIME4);
+        pos += 4;
+    }
+    for byte in data[pos..].iter(){
+        hash = hash.wrapping_add((*byte as u32).wrapping_mul(PRIME5)).rotate_left(11).wrapping_mul(PRIME1);
+    }
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(PRIME2);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(PRIME3);
+    hash ^= hash >> 16;
+    hash
+}
@@ -0,0 +1,128 @@
+use crate::{alloc_page_phys_addr, free_pages, phys_addr_to_limine_virtual_addr};
+
+pub mod inflate;
+pub mod gzip;
+pub mod lz4;
+
+const PAGE_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error{
+    Truncated,
+    InvalidData(&'static str),
+    ChecksumMismatch,
+    OutOfMemory,
+}
+
+// Decompressed data. It is kept in contiguous physical pages because the kernel heap is too small
+// for an initrd, and grows like a Vec by moving to twice as many pages.
+pub struct Output{
+    ptr: *mut u8,
+    pages: usize,
+    len: usize,
+}
+
+impl Output{
+    pub fn new() -> Self{
+        Output { ptr: core::ptr::null_mut(), pages: 0, len: 0 }
+    }
+
+    pub fn len(&self) -> usize{
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.len == 0
+    }
+
+    pub fn as_slice(&self) -> &[u8]{
+        if self.ptr.is_null(){
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    // The pages are never freed, used for data needed until shutdown
+    pub fn leak(self) -> &'static [u8]{
+        let output = core::mem::ManuallyDrop::new(self);
+        if output.ptr.is_null(){
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(output.ptr, output.len) }
+    }
+
+    // Make room for additional bytes, the content is moved if new pages are needed
+    pub fn reserve(&mut self, additional: usize) -> Result<(), Error>{
+        let needed = self.len.checked_add(additional).ok_or(Error::OutOfMemory)?;
+        if needed <= self.pages * PAGE_SIZE{
+            return Ok(());
+        }
+        let pages = needed.div_ceil(PAGE_SIZE).max(self.pages * 2);
+        let phys_addr = unsafe { alloc_page_phys_addr(pages) } as usize;
+        if phys_addr == 0{
+            return Err(Error::OutOfMemory);
+        }
+        let ptr = unsafe { phys_addr_to_limine_virtual_addr(phys_addr) } as *mut u8;
+        if !self.ptr.is_null(){
+            unsafe {
+                core::ptr::copy_nonoverlapping(self.ptr, ptr, self.len);
+                free_pages(self.ptr as *mut core::ffi::c_void, self.pages);
+            }
+        }
+        self.ptr = ptr;
+        self.pages = pages;
+        Ok(())
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), Error>{
+        self.reserve(1)?;
+        unsafe { self.ptr.add(self.len).write(byte) };
+        self.len += 1;
+        Ok(())
+    }
+
+    fn extend_from_slice(&mut self, data: &[u8]) -> Result<(), Error>{
+        self.reserve(data.len())?;
+        unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), self.ptr.add(self.len), data.len()) };
+        self.len += data.len();
+        Ok(())
+    }
+
+    // Append len bytes copied from distance bytes before the end, the ranges can overlap
+    fn copy_match(&mut self, distance: usize, len: usize) -> Result<(), Error>{
+        if distance == 0 || distance > self.len{
+            return Err(Error::InvalidData("match distance too far back"));
+        }
+        self.reserve(len)?;
+        for _ in 0..len{
+            unsafe { self.ptr.add(self.len).write(self.ptr.add(self.len - distance).read()) };
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Output{
+    fn default() -> Self{
+        Output::new()
+    }
+}
+
+impl Drop for Output{
+    fn drop(&mut self){
+        if !self.ptr.is_null(){
+            unsafe { free_pages(self.ptr as *mut core::ffi::c_void, self.pages) };
+        }
+    }
+}
+
+// Decompressed content if data starts with the magic of a gzip or LZ4 stream, None otherwise
+pub fn decompress(data: &[u8]) -> Result<Option<Output>, Error>{
+    if gzip::is_gzip(data){
+        gzip::decompress(data).map(Some)
+    }else if lz4::is_lz4(data){
+        lz4::decompress(data).map(Some)
+    }else{
+        Ok(None)
+    }
+}
@@ -1,68 +1,83 @@
-use core::slice;
+use core::{ffi::c_void, slice};
 
 use elf::{endian::AnyEndian, segment::ProgramHeader, ElfBytes};
 
-use crate::{alloc_page_phys_addr, map_page_current, PTE_PRESENT, PTE_READ_WRITE, PTE_USER_SUPERVISOR};
+use crate::{errno::Errno, alloc_page_phys_addr, free_pages, phys_addr_to_limine_virtual_addr, scheduler::address_space::AddressSpace, uaccess::USER_SPACE_END, PTE_PRESENT, PTE_READ_WRITE, PTE_USER_SUPERVISOR};
 
 const PAGE_SIZE: usize = 4096;
 
-pub fn load_elf_file(data: &[u8]) -> (usize, usize, usize, usize){
-    let file = ElfBytes::<AnyEndian>::minimal_parse(data).unwrap();
-    let segments = file.segments().unwrap();
+// Load the file in the given address space, the pages already mapped are freed
+// with the address space if it fails
+pub fn load_elf_file(data: &[u8], address_space: &AddressSpace) -> Result<(usize, usize, usize, usize), Errno>{
+    let file = ElfBytes::<AnyEndian>::minimal_parse(data).map_err(|_| Errno::ENOEXEC)?;
+    let segments = file.segments().ok_or(Errno::ENOEXEC)?;
     let mut end_of_segments = 0;
     for segment in segments{
         if segment.p_type == 1{
-            let current_segment_end = load_segment(&segment, data);
+            let current_segment_end = load_segment(&segment, data, address_space)?;
             if current_segment_end > end_of_segments{
                 end_of_segments = current_segment_end;
             }
         }
     }
-    let stack_start = alloc_memory_zone(end_of_segments as usize, 2);
+    let stack_start = alloc_memory_zone(address_space, end_of_segments, 2)?;
     let sp = stack_start + 2 * PAGE_SIZE - 16;
     let heap_len = 16 * PAGE_SIZE;
-    let heap_addr = alloc_memory_zone(stack_start + 3 * PAGE_SIZE, heap_len / PAGE_SIZE);
-    (file.ehdr.e_entry as usize, sp, heap_addr, heap_len)
+    let heap_addr = alloc_memory_zone(address_space, stack_start + 3 * PAGE_SIZE, heap_len / PAGE_SIZE)?;
+    Ok((file.ehdr.e_entry as usize, sp, heap_addr, heap_len))
 }
 
+// Physical pages are contiguous so they are accessed through the HHDM,
+// this way the address space doesn't need to be the current one
+fn physical_zone(phys_addr: usize, page_count: usize) -> &'static mut [u8]{
+    let virt_addr = unsafe { phys_addr_to_limine_virtual_addr(phys_addr) };
+    unsafe { slice::from_raw_parts_mut(virt_addr as *mut u8, page_count * PAGE_SIZE) }
+}
 
-pub fn load_segment(header: &ProgramHeader, data: &[u8]) -> usize{
-    let alloc_size = header.p_memsz;
-    let mut page_count = alloc_size / 4096;
-    if (alloc_size % 4096) != 0{
-        page_count += 1; // Add one page if alloc doesn't end on page boundary
+pub fn load_segment(header: &ProgramHeader, data: &[u8], address_space: &AddressSpace) -> Result<usize, Errno>{
+    let virt_addr = header.p_vaddr as usize;
+    let file_start = header.p_offset as usize;
+    let file_size = header.p_filesz as usize;
+    // The segment must stay in the user half and its content in the file
+    let segment_end = virt_addr.checked_add(header.p_memsz as usize).ok_or(Errno::ENOEXEC)?;
+    let file_end = file_start.checked_add(file_size).ok_or(Errno::ENOEXEC)?;
+    if segment_end as u64 > USER_SPACE_END || file_end > data.len() || file_size > header.p_memsz as usize{
+        return Err(Errno::ENOEXEC);
     }
-    let pages = unsafe { alloc_page_phys_addr(page_count.try_into().unwrap()) };
-    for i in 0..page_count{
-        let offset = (i * 4096) as usize;
-        let phys_addr = (pages as usize) + offset;
-        let virt_addr = (header.p_vaddr as usize) + offset;
-        unsafe{
-            map_page_current(phys_addr, virt_addr, PTE_PRESENT| PTE_READ_WRITE | PTE_USER_SUPERVISOR)
-        }
+    let page_start = virt_addr - (virt_addr % PAGE_SIZE);
+    let page_count = (segment_end - page_start).div_ceil(PAGE_SIZE);
+    let pages = unsafe { alloc_page_phys_addr(page_count) } as usize;
+    if pages == 0{
+        return Err(Errno::ENOMEM);
     }
+    map_user_pages(address_space, pages, page_start, page_count)?;
 
-    let start_addr = header.p_vaddr as *mut u8;
-    let slice = unsafe { slice::from_raw_parts_mut(start_addr, header.p_memsz as usize) };
-    for i in 0..slice.len(){
-        slice[i] = 0;
-    }
-    for i in 0..header.p_filesz as usize{
-        let offset = (header.p_offset as usize) + i;
-        slice[i] = data[offset];
+    let zone = physical_zone(pages, page_count);
+    zone.fill(0);
+    let start = virt_addr - page_start;
+    zone[start..start + file_size].copy_from_slice(&data[file_start..file_end]);
+    Ok(page_start + page_count * PAGE_SIZE) //return end of segment
+}
+
+pub fn alloc_memory_zone(address_space: &AddressSpace, base_addr: usize, page_count: usize) -> Result<usize, Errno>{
+    let pages = unsafe {alloc_page_phys_addr(page_count)} as usize;
+    if pages == 0{
+        return Err(Errno::ENOMEM);
     }
-    let virt_addr = header.p_vaddr;
-    let page_start = virt_addr - (virt_addr%4096);
-    (page_start + 4096*page_count) as usize //return end of segment
+    physical_zone(pages, page_count).fill(0);
+    map_user_pages(address_space, pages, base_addr, page_count)?;
+    Ok(base_addr)
 }
 
-pub fn alloc_memory_zone(base_addr: usize, page_count: usize) -> usize{
-    let pages = unsafe {alloc_page_phys_addr(page_count)};
+// Map the contiguous physical pages at virt_addr. The pages that are mapped are freed with the
+// address space, the others are freed here if a page table can't be allocated
+fn map_user_pages(address_space: &AddressSpace, pages: usize, virt_addr: usize, page_count: usize) -> Result<(), Errno>{
     for i in 0..page_count{
-        let offset = (i * PAGE_SIZE) as usize;
-        let phys_addr = (pages as usize) + offset;
-        let virt_addr = (base_addr as usize) + offset;
-        unsafe { map_page_current(phys_addr, virt_addr, PTE_PRESENT | PTE_READ_WRITE | PTE_USER_SUPERVISOR); }
+        let offset = i * PAGE_SIZE;
+        if let Err(errno) = address_space.map(pages + offset, virt_addr + offset, PTE_PRESENT | PTE_READ_WRITE | PTE_USER_SUPERVISOR){
+            unsafe { free_pages(phys_addr_to_limine_virtual_addr(pages + offset) as *mut c_void, page_count - i) };
+            return Err(errno);
+        }
     }
-    base_addr
-}
\ No newline at end of file
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use crate::fs::vfs;
+
+// Errors returned by the syscalls, rax holds the negated value.
+// The numbers are the same as in library/src/errno.h
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i64)]
+pub enum Errno{
+    EPERM = 1,
+    ENOENT = 2,
+    ESRCH = 3,
+    EIO = 5,
+    ENOEXEC = 8,
+    EBADF = 9,
+    ECHILD = 10,
+    ENOMEM = 12,
+    EFAULT = 14,
+    EBUSY = 16,
+    EEXIST = 17,
+    ENODEV = 19,
+    ENOTDIR = 20,
+    EISDIR = 21,
+    EINVAL = 22,
+    EMFILE = 24,
+    EFBIG = 27,
+    ENOSPC = 28,
+    EROFS = 30,
+    ERANGE = 34,
+    ENAMETOOLONG = 36,
+    ENOSYS = 38,
+    ENOTEMPTY = 39,
+    ELOOP = 40,
+}
+
+pub type SyscallResult = Result<u64, Errno>;
+
+// Value written in rax, errors are in [-4095, -1] so they can't be confused with a pointer
+pub fn to_return_value(result: SyscallResult) -> u64{
+    match result{
+        Ok(value) => value,
+        Err(errno) => (-(errno as i64)) as u64,
+    }
+}
+
+impl From<vfs::Error> for Errno{
+    fn from(error: vfs::Error) -> Self{
+        match error{
+            vfs::Error::NotFound => Errno::ENOENT,
+            vfs::Error::FileAlreadyExist => Errno::EEXIST,
+            vfs::Error::NotAFolder => Errno::ENOTDIR,
+            vfs::Error::NotAMountpoint => Errno::EINVAL,
+            vfs::Error::NotAReadableFile => Errno::EISDIR,
+            vfs::Error::IsAFolder => Errno::EISDIR,
+            vfs::Error::NotEmpty => Errno::ENOTEMPTY,
+            vfs::Error::Busy => Errno::EBUSY,
+            vfs::Error::ReadOnly => Errno::EROFS,
+            vfs::Error::FileTooBig => Errno::EFBIG,
+            vfs::Error::TooManyLinks => Errno::ELOOP,
+            vfs::Error::Io => Errno::EIO,
+            vfs::Error::NoSpace => Errno::ENOSPC,
+            vfs::Error::OutOfMemory => Errno::ENOMEM,
+        }
+    }
+}
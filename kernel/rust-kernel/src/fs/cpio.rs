@@ -0,0 +1,194 @@
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec::Vec};
+
+use super::vfs::{self, FsDriver, Inode, Metadata, MountPoint, PathBuf};
+
+// SVR4 "newc" archives, made with cpio -H newc. Each entry is a 110 bytes ASCII header
+// followed by the name and the data, both padded to 4 bytes. The last entry is named TRAILER!!!
+const HEADER_SIZE: usize = 110;
+const TRAILER_NAME: &str = "TRAILER!!!";
+
+const MODE_TYPE_MASK: u32 = 0o170000;
+const MODE_FOLDER: u32 = 0o040000;
+const MODE_REGULAR_FILE: u32 = 0o100000;
+const MODE_SYMLINK: u32 = 0o120000;
+
+// "070702" archives also have a checksum of the data, which isn't checked
+pub fn is_cpio(data: &[u8]) -> bool{
+    data.starts_with(b"070701") || data.starts_with(b"070702")
+}
+
+// Why an archive can't be loaded, offset is the position of the header in the archive
+#[derive(Debug)]
+pub enum ParseError{
+    Truncated { offset: usize },
+    InvalidMagic { offset: usize },
+    InvalidNumber { offset: usize, field: &'static str },
+    NotAFolder { path: String }, // An entry is inside something that isn't a folder
+}
+
+struct Header{
+    name: String,
+    ino: u32,
+    mode: u32,
+    nlink: u32,
+    dev: (u32, u32),
+    size: usize,
+    start_addr: usize,
+    metadata: Metadata,
+}
+
+// Position of the content of a file in the archive
+#[derive(Debug, Clone, Copy)]
+struct Entry{
+    start_addr: usize,
+    size: usize,
+    metadata: Metadata,
+}
+
+impl Entry{
+    fn new_folder() -> Self{
+        Entry { start_addr: 0, size: 0, metadata: Metadata::new_default(true) }
+    }
+}
+
+fn parse_hex(field: &[u8]) -> Option<u32>{
+    u32::from_str_radix(core::str::from_utf8(field).ok()?, 16).ok()
+}
+
+// The header and the position of the next one
+fn parse_header(data: &[u8], start: usize) -> Result<(Header, usize), ParseError>{
+    let block = start.checked_add(HEADER_SIZE).and_then(|end| data.get(start..end))
+        .ok_or(ParseError::Truncated { offset: start })?;
+    if !is_cpio(block){
+        return Err(ParseError::InvalidMagic { offset: start });
+    }
+    // The fields are 8 hexadecimal digits after the 6 bytes of magic
+    let field = |index: usize, name: &'static str| {
+        let field_start = 6 + index * 8;
+        parse_hex(&block[field_start..field_start + 8]).ok_or(ParseError::InvalidNumber { offset: start, field: name })
+    };
+    let ino = field(0, "ino")?;
+    let mode = field(1, "mode")?;
+    let metadata = Metadata {
+        mode: mode & 0o7777,
+        uid: field(2, "uid")?,
+        gid: field(3, "gid")?,
+        mtime: field(5, "mtime")? as u64,
+    };
+    let nlink = field(4, "nlink")?;
+    let size = field(6, "filesize")? as usize;
+    let dev = (field(7, "devmajor")?, field(8, "devminor")?);
+    let name_size = field(11, "namesize")? as usize;
+
+    let name_start = start + HEADER_SIZE;
+    let name = name_start.checked_add(name_size).and_then(|end| data.get(name_start..end))
+        .ok_or(ParseError::Truncated { offset: start })?;
+    // The name size counts the NUL terminator
+    let name = String::from_utf8_lossy(name.split(|c| *c == 0).next().unwrap_or_default()).into_owned();
+    let start_addr = (name_start + name_size).next_multiple_of(4);
+    let end = start_addr.checked_add(size).filter(|end| *end <= data.len())
+        .ok_or(ParseError::Truncated { offset: start })?;
+    let header = Header { name, ino, mode, nlink, dev, size, start_addr, metadata };
+    Ok((header, end.next_multiple_of(4)))
+}
+
+// Build a read-only file system from the archive
+pub fn parse_file(data: &'static [u8]) -> Result<MountPoint, ParseError>{
+    let mut driver = CpioDriver { entries: BTreeMap::new(), data };
+    let root_id = driver.add_entry(Entry::new_folder());
+    let mut root = Inode::new_folder(root_id);
+    // Files with several names share an inode number, newc only stores their data once
+    let mut hard_links: BTreeMap<(u32, u32, u32), Vec<usize>> = BTreeMap::new();
+    let mut pos = 0;
+    loop{
+        let (header, next) = parse_header(data, pos)?;
+        pos = next;
+        if header.name == TRAILER_NAME{
+            break;
+        }
+        let entry = Entry { start_addr: header.start_addr, size: header.size, metadata: header.metadata };
+        let mut path = PathBuf::from(header.name.as_ref());
+        let Some(name) = path.split_last_component() else{
+            // "." is the root
+            if header.mode & MODE_TYPE_MASK == MODE_FOLDER{
+                driver.entries.insert(root_id, entry);
+            }
+            continue;
+        };
+        let not_a_folder = || ParseError::NotAFolder { path: header.name.clone() };
+        let parent = root.find_or_create_folder(&path, || driver.add_entry(Entry::new_folder())).map_err(|_| not_a_folder())?;
+        let node = match header.mode & MODE_TYPE_MASK{
+            MODE_REGULAR_FILE => {
+                let id = driver.add_entry(entry);
+                if header.nlink > 1{
+                    hard_links.entry((header.ino, header.dev.0, header.dev.1)).or_default().push(id);
+                }
+                Inode::new_file(id)
+            },
+            MODE_FOLDER => {
+                // The folder can already have been created for an earlier entry inside it
+                if let Ok(folder) = parent.search_in_folder(&name) && folder.is_folder(){
+                    driver.entries.insert(folder.get_id(), entry);
+                    continue;
+                }
+                Inode::new_folder(driver.add_entry(entry))
+            },
+            MODE_SYMLINK => {
+                let target = &data[header.start_addr..header.start_addr + header.size];
+                let target = String::from_utf8_lossy(target).into_owned();
+                Inode::new_symlink(driver.add_entry(entry), target)
+            },
+            // Devices, fifos and sockets
+            _ => continue,
+        };
+        parent.replace_in_folder(node, name).map_err(|_| not_a_folder())?;
+    }
+    // All the names of a file read the data stored with one of them
+    for ids in hard_links.values(){
+        if let Some(data_entry) = ids.iter().map(|id| driver.entries[id]).find(|entry| entry.size != 0){
+            for id in ids{
+                driver.entries.insert(*id, data_entry);
+            }
+        }
+    }
+    Ok(MountPoint::new(root, driver))
+}
+
+// The archive stays in memory, its files are read in place
+pub struct CpioDriver{
+    entries: BTreeMap<usize, Entry>,
+    data: &'static [u8],
+}
+
+impl CpioDriver{
+    // Returns the id of the new entry
+    fn add_entry(&mut self, entry: Entry) -> usize{
+        let id = self.entries.last_key_value().map_or(0, |(id, _)| id + 1);
+        self.entries.insert(id, entry);
+        id
+    }
+
+    fn get_entry(&self, id: usize) -> Result<&Entry, vfs::Error>{
+        self.entries.get(&id).ok_or(vfs::Error::NotFound)
+    }
+}
+
+impl FsDriver for CpioDriver{
+    fn get_size(&self, node: &Inode) -> Result<usize, vfs::Error> {
+        Ok(self.get_entry(node.get_id())?.size)
+    }
+
+    fn get_metadata(&self, node: &Inode) -> Result<Metadata, vfs::Error> {
+        Ok(self.get_entry(node.get_id())?.metadata)
+    }
+
+    fn read(&self, node: &Inode, pos: usize, requested_amount: usize) -> Result<Box<[u8]>, vfs::Error> {
+        if node.is_folder(){
+            return Err(vfs::Error::NotAReadableFile);
+        }
+        let entry = self.get_entry(node.get_id())?;
+        let start = pos.min(entry.size);
+        let end = start.saturating_add(requested_amount).min(entry.size);
+        Ok(Box::from(&self.data[entry.start_addr + start..entry.start_addr + end]))
+    }
+}
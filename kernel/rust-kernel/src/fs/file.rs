@@ -0,0 +1,91 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::errno::Errno;
+
+const MAX_OPEN_FILES: usize = 64;
+
+// Flags of the open syscall, same values as in library/src/lib.h
+pub const O_RDONLY: u64 = 0;
+pub const O_WRONLY: u64 = 1;
+pub const O_RDWR: u64 = 2;
+pub const O_ACCMODE: u64 = 3;
+pub const O_CREAT: u64 = 0x40;
+pub const O_TRUNC: u64 = 0x200;
+pub const O_APPEND: u64 = 0x400;
+pub const O_VALID_FLAGS: u64 = O_ACCMODE | O_CREAT | O_TRUNC | O_APPEND;
+
+// File opened by a process, the path is resolved again on each access
+// so that no reference to the file tree is kept between two syscalls
+#[derive(Debug, Clone)]
+pub struct OpenFile{
+    path: String,
+    offset: usize,
+    flags: u64,
+}
+
+impl OpenFile{
+    pub fn new(path: String, flags: u64) -> Self{
+        OpenFile { path, offset: 0, flags }
+    }
+
+    pub fn can_read(&self) -> bool{
+        self.flags & O_ACCMODE != O_WRONLY
+    }
+
+    pub fn can_write(&self) -> bool{
+        self.flags & O_ACCMODE != O_RDONLY
+    }
+
+    pub fn is_append(&self) -> bool{
+        self.flags & O_APPEND != 0
+    }
+
+    pub fn get_path(&self) -> &str{
+        &self.path
+    }
+
+    pub fn get_offset(&self) -> usize{
+        self.offset
+    }
+
+    pub fn set_offset(&mut self, offset: usize){
+        self.offset = offset;
+    }
+}
+
+// File descriptors of a process, copied on fork and kept on exec
+#[derive(Debug, Clone, Default)]
+pub struct FileTable{
+    files: Vec<Option<OpenFile>>,
+}
+
+impl FileTable{
+    pub fn new() -> Self{
+        FileTable { files: Vec::new() }
+    }
+
+    // Returns the lowest free descriptor
+    pub fn insert(&mut self, file: OpenFile) -> Result<usize, Errno>{
+        if let Some(fd) = self.files.iter().position(|file| file.is_none()){
+            self.files[fd] = Some(file);
+            return Ok(fd);
+        }
+        if self.files.len() >= MAX_OPEN_FILES{
+            return Err(Errno::EMFILE);
+        }
+        self.files.push(Some(file));
+        Ok(self.files.len() - 1)
+    }
+
+    pub fn get(&self, fd: usize) -> Result<&OpenFile, Errno>{
+        self.files.get(fd).and_then(|file| file.as_ref()).ok_or(Errno::EBADF)
+    }
+
+    pub fn get_mut(&mut self, fd: usize) -> Result<&mut OpenFile, Errno>{
+        self.files.get_mut(fd).and_then(|file| file.as_mut()).ok_or(Errno::EBADF)
+    }
+
+    pub fn remove(&mut self, fd: usize) -> Result<OpenFile, Errno>{
+        self.files.get_mut(fd).and_then(|file| file.take()).ok_or(Errno::EBADF)
+    }
+}
@@ -1,2 +1,181 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+use spin::Mutex;
+use zerocopy::{Immutable, IntoBytes};
+
+use mount::MountTable;
+use vfs::{MountPoint, PathBuf};
+
 pub mod vfs;
-pub mod ustar;
\ No newline at end of file
+pub mod ustar;
+pub mod file;
+pub mod tmpfs;
+pub mod mount;
+pub mod cpio;
+pub mod raw;
+
+static MOUNTS: Mutex<MountTable> = Mutex::new(MountTable::new());
+
+pub const FILE_TYPE_REGULAR: u64 = 1;
+pub const FILE_TYPE_FOLDER: u64 = 2;
+pub const FILE_TYPE_SYMLINK: u64 = 3;
+
+// Value of file_type in Stat and in the records of getdents
+pub fn get_file_type(kind: vfs::FileKind) -> u64{
+    match kind{
+        vfs::FileKind::RegularFile => FILE_TYPE_REGULAR,
+        vfs::FileKind::Folder => FILE_TYPE_FOLDER,
+        vfs::FileKind::SymLink => FILE_TYPE_SYMLINK,
+    }
+}
+
+// Returned by the fstat syscall, same layout as struct stat in library/src/lib.h
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, IntoBytes, Immutable)]
+pub struct Stat{
+    pub size: u64,
+    pub file_type: u64,
+    pub mode: u64,
+    pub uid: u64,
+    pub gid: u64,
+    pub mtime: u64,
+}
+
+#[derive(Debug)]
+pub enum ArchiveError{
+    Ustar(ustar::ParseError),
+    Cpio(cpio::ParseError),
+    UnknownFormat,
+}
+
+// Read-only file system with the content of an initrd, the archive format is found from its magic
+pub fn load_archive(data: &'static [u8]) -> Result<MountPoint, ArchiveError>{
+    if cpio::is_cpio(data){
+        cpio::parse_file(data).map_err(ArchiveError::Cpio)
+    }else if ustar::is_ustar(data){
+        ustar::parse_file(data).and_then(|headers| ustar::headers_to_fs(headers, data)).map_err(ArchiveError::Ustar)
+    }else{
+        Err(ArchiveError::UnknownFormat)
+    }
+}
+
+// Run f with the mount point containing the path and the path relative to its root,
+// the symlinks on the path are followed
+fn with_mountpoint<T>(path: PathBuf, f: impl FnOnce(&MountPoint, PathBuf) -> Result<T, vfs::Error>) -> Result<T, vfs::Error>{
+    let mounts = MOUNTS.lock();
+    let path = mounts.canonicalize(path, true)?;
+    let (mountpoint, path) = mounts.resolve(path)?;
+    f(mountpoint, path)
+}
+
+fn with_mountpoint_mut<T>(path: PathBuf, f: impl FnOnce(&mut MountPoint, PathBuf) -> Result<T, vfs::Error>) -> Result<T, vfs::Error>{
+    let mut mounts = MOUNTS.lock();
+    let path = mounts.canonicalize(path, true)?;
+    let (mountpoint, path) = mounts.resolve_mut(path)?;
+    f(mountpoint, path)
+}
+
+// Path without symlinks, except the last component which is kept if it is one
+fn canonicalize_parent(path: &str) -> Result<PathBuf, vfs::Error>{
+    MOUNTS.lock().canonicalize(PathBuf::from(path), false)
+}
+
+// The parent folder and the name of the last component
+fn split_parent(mut parent: PathBuf) -> Result<(PathBuf, String), vfs::Error>{
+    // The root has no parent and always exists
+    let name = parent.split_last_component().ok_or(vfs::Error::FileAlreadyExist)?;
+    Ok((parent, name))
+}
+
+pub fn stat(path: &str) -> Result<Stat, vfs::Error>{
+    with_mountpoint(PathBuf::from(path), |mountpoint, path| {
+        let file_type = get_file_type(mountpoint.get_root().find(path.clone())?.get_kind());
+        let metadata = mountpoint.get_metadata(path.clone())?;
+        Ok(Stat {
+            size: mountpoint.get_size(path)? as u64,
+            file_type,
+            mode: metadata.mode as u64,
+            uid: metadata.uid as u64,
+            gid: metadata.gid as u64,
+            mtime: metadata.mtime,
+        })
+    })
+}
+
+// Read at most len bytes from pos, less are returned at the end of the file
+pub fn read(path: &str, pos: usize, len: usize) -> Result<Box<[u8]>, vfs::Error>{
+    with_mountpoint(PathBuf::from(path), |mountpoint, path| mountpoint.read(path, pos, len))
+}
+
+// The file systems mounted in the folder are listed with the type of their root
+pub fn read_dir(path: &str) -> Result<Vec<vfs::DirEntry>, vfs::Error>{
+    let mounts = MOUNTS.lock();
+    let path = mounts.canonicalize(PathBuf::from(path), true)?;
+    let (mountpoint, relative) = mounts.resolve(path.clone())?;
+    let mut entries = mountpoint.read_dir(relative)?;
+    for child in mounts.get_mounted_children(&path){
+        entries.retain(|entry| entry.get_name() != child.get_name());
+        entries.push(child);
+    }
+    entries.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+    Ok(entries)
+}
+
+// Read a whole file of the root filesystem
+pub fn read_file(path: &str) -> Result<Box<[u8]>, vfs::Error>{
+    let size = stat(path)?.size as usize;
+    read(path, 0, size)
+}
+
+pub fn write(path: &str, pos: usize, data: &[u8]) -> Result<usize, vfs::Error>{
+    with_mountpoint_mut(PathBuf::from(path), |mountpoint, path| mountpoint.write(path, pos, data))
+}
+
+pub fn truncate(path: &str, size: usize) -> Result<(), vfs::Error>{
+    with_mountpoint_mut(PathBuf::from(path), |mountpoint, path| mountpoint.truncate(path, size))
+}
+
+fn create(path: &str, folder: bool) -> Result<(), vfs::Error>{
+    let path = canonicalize_parent(path)?;
+    // A mount point can be on a path missing from its parent file system
+    if MOUNTS.lock().is_mountpoint(&path){
+        return Err(vfs::Error::FileAlreadyExist);
+    }
+    let (parent, name) = split_parent(path)?;
+    with_mountpoint_mut(parent, |mountpoint, parent| mountpoint.create(parent, name, folder))
+}
+
+pub fn create_file(path: &str) -> Result<(), vfs::Error>{
+    create(path, false)
+}
+
+pub fn mkdir(path: &str) -> Result<(), vfs::Error>{
+    create(path, true)
+}
+
+pub fn unlink(path: &str) -> Result<(), vfs::Error>{
+    // A symlink is removed, not its target
+    let path = canonicalize_parent(path)?;
+    {
+        // The folders containing a mount point must stay
+        let mounts = MOUNTS.lock();
+        if mounts.is_mountpoint(&path) || !mounts.get_mounted_children(&path).is_empty(){
+            return Err(vfs::Error::Busy);
+        }
+    }
+    let (parent, name) = split_parent(path)?;
+    with_mountpoint_mut(parent, |mountpoint, parent| mountpoint.unlink(parent, &name))
+}
+
+// Attach a file system at path, for example the one returned by tmpfs::new_tmpfs.
+// The root file system is mounted at "/"
+pub fn mount(path: &str, filesystem: MountPoint) -> Result<(), vfs::Error>{
+    let mut mounts = MOUNTS.lock();
+    let path = mounts.canonicalize(PathBuf::from(path), true)?;
+    mounts.mount(path, filesystem)
+}
+
+pub fn umount(path: &str) -> Result<MountPoint, vfs::Error>{
+    let mut mounts = MOUNTS.lock();
+    let path = mounts.canonicalize(PathBuf::from(path), true)?;
+    mounts.umount(&path)
+}
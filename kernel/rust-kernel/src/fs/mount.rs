@@ -0,0 +1,136 @@
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+use super::vfs::{DirEntry, Error, Inode, MountPoint, PathBuf};
+
+// Number of symlinks that can be followed while resolving a path, like on Linux
+const MAX_SYMLINKS: usize = 40;
+
+// File systems attached to the file tree, indexed by the components of their path.
+// A path is handled by the mount point with the longest matching prefix, so a mount
+// hides what was at its path in the parent file system until it is unmounted.
+#[derive(Default)]
+pub struct MountTable{
+    mounts: BTreeMap<Vec<String>, MountPoint>,
+}
+
+fn to_key(path: &PathBuf) -> Vec<String>{
+    path.get_components().cloned().collect()
+}
+
+fn to_path(key: &[String]) -> PathBuf{
+    let mut path = PathBuf::new();
+    for component in key{
+        path.push_component(component.clone());
+    }
+    path
+}
+
+// Path with the symlinks replaced by their target. A symlink at the end of the path is
+// only followed if follow_last, the path can end with missing components.
+// get_root returns the root of the file tree containing a path and the number of
+// components of the path that lead to that root
+pub fn follow_symlinks<'a>(mut path: PathBuf, follow_last: bool, get_root: impl Fn(&[String]) -> Result<(usize, &'a Inode), Error>) -> Result<PathBuf, Error>{
+    for _ in 0..=MAX_SYMLINKS{
+        let key = to_key(&path);
+        let (depth, root) = get_root(&key)?;
+        let Some((index, target)) = root.find_symlink(&to_path(&key[depth..]), follow_last) else{
+            return Ok(path);
+        };
+        // The target is relative to the folder containing the symlink
+        let index = depth + index;
+        path = to_path(&key[..index]).join(&target);
+        for component in key[index + 1..].iter(){
+            path.push_component(component.clone());
+        }
+    }
+    Err(Error::TooManyLinks)
+}
+
+impl MountTable{
+    pub const fn new() -> Self{
+        MountTable { mounts: BTreeMap::new() }
+    }
+
+    pub fn is_mountpoint(&self, path: &PathBuf) -> bool{
+        self.mounts.contains_key(&to_key(path))
+    }
+
+    // Length of the longest prefix of the path where something is mounted
+    fn mount_depth(&self, key: &[String]) -> Result<usize, Error>{
+        (0..=key.len()).rev().find(|depth| self.mounts.contains_key(&key[..*depth])).ok_or(Error::NotFound)
+    }
+
+    // Mount point containing the path, with the path relative to its root
+    pub fn resolve(&self, path: PathBuf) -> Result<(&MountPoint, PathBuf), Error>{
+        let key = to_key(&path);
+        let depth = self.mount_depth(&key)?;
+        Ok((&self.mounts[&key[..depth]], to_path(&key[depth..])))
+    }
+
+    pub fn resolve_mut(&mut self, path: PathBuf) -> Result<(&mut MountPoint, PathBuf), Error>{
+        let key = to_key(&path);
+        let depth = self.mount_depth(&key)?;
+        Ok((self.mounts.get_mut(&key[..depth]).unwrap(), to_path(&key[depth..])))
+    }
+
+    // Path with the symlinks of every mounted file system replaced by their target
+    pub fn canonicalize(&self, path: PathBuf, follow_last: bool) -> Result<PathBuf, Error>{
+        follow_symlinks(path, follow_last, |key| {
+            let depth = self.mount_depth(key)?;
+            Ok((depth, self.mounts[&key[..depth]].get_root()))
+        })
+    }
+
+    // File systems mounted directly in the folder at path
+    pub fn get_mounted_children(&self, path: &PathBuf) -> Vec<DirEntry>{
+        let key = to_key(path);
+        self.mounts.iter()
+            .filter(|(mount, _)| mount.len() == key.len() + 1 && mount.starts_with(&key))
+            .map(|(mount, filesystem)| DirEntry::new(mount[key.len()].clone(), filesystem.get_root().get_kind()))
+            .collect()
+    }
+
+    // The parent of path must be a folder, the path itself can be missing or of the same
+    // type as the root of the file system. A file system made of a single file can't be the root
+    pub fn mount(&mut self, path: PathBuf, filesystem: MountPoint) -> Result<(), Error>{
+        if self.is_mountpoint(&path){
+            return Err(Error::Busy);
+        }
+        let is_folder = filesystem.get_root().is_folder();
+        if path.is_empty(){
+            if !is_folder{
+                return Err(Error::NotAFolder);
+            }
+        }else{
+            let mut parent = path.clone();
+            parent.split_last_component();
+            let (mountpoint, parent) = self.resolve(parent)?;
+            if !mountpoint.get_root().find(parent)?.is_folder(){
+                return Err(Error::NotAFolder);
+            }
+            let (mountpoint, target) = self.resolve(path.clone())?;
+            match mountpoint.get_root().find(target){
+                Ok(node) if node.is_folder() != is_folder => {
+                    return Err(if is_folder { Error::NotAFolder } else { Error::IsAFolder });
+                },
+                Ok(_) | Err(Error::NotFound) => {},
+                Err(error) => return Err(error),
+            }
+        }
+        self.mounts.insert(to_key(&path), filesystem);
+        Ok(())
+    }
+
+    // Detach the file system mounted at path, the root and file systems with
+    // other mounts inside them can't be unmounted
+    pub fn umount(&mut self, path: &PathBuf) -> Result<MountPoint, Error>{
+        let key = to_key(path);
+        if !self.mounts.contains_key(&key){
+            return Err(Error::NotAMountpoint);
+        }
+        if key.is_empty() || self.mounts.keys().any(|mount| mount.len() > key.len() && mount.starts_with(&key)){
+            return Err(Error::Busy);
+        }
+        Ok(self.mounts.remove(&key).unwrap())
+    }
+}
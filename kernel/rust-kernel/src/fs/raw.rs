@@ -0,0 +1,38 @@
+use alloc::{boxed::Box, vec};
+
+use crate::block::{cache::{self, DeviceId}, memory_disk::MemoryDisk};
+use super::vfs::{self, FsDriver, Inode, MountPoint};
+
+// A boot module that isn't an archive, mounted as a single read-only file.
+// It is read through the page cache like a disk
+pub struct RawDriver{
+    device: DeviceId,
+    size: usize, // The device is rounded up to whole sectors
+}
+
+// Returns a file system whose root is a file with the content of data
+pub fn new_raw_file(data: &'static [u8]) -> MountPoint{
+    let device = cache::register_device(Box::new(MemoryDisk::new(data)));
+    MountPoint::new(Inode::new_file(0), RawDriver { device, size: data.len() })
+}
+
+impl FsDriver for RawDriver{
+    fn get_size(&self, _node: &Inode) -> Result<usize, vfs::Error> {
+        Ok(self.size)
+    }
+
+    fn read(&self, _node: &Inode, pos: usize, requested_amount: usize) -> Result<Box<[u8]>, vfs::Error> {
+        let start = pos.min(self.size);
+        let end = start.saturating_add(requested_amount).min(self.size);
+        let mut buffer = vec![0; end - start];
+        cache::read(self.device, start as u64, &mut buffer)?;
+        Ok(buffer.into_boxed_slice())
+    }
+}
+
+impl Drop for RawDriver{
+    // Unmounted, the cached blocks of the module are freed
+    fn drop(&mut self){
+        let _ = cache::unregister_device(self.device);
+    }
+}
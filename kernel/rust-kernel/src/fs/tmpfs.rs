@@ -0,0 +1,125 @@
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+
+use super::vfs::{self, FsDriver, Inode, MountPoint};
+
+// Files are kept in the kernel heap, which is 16 MiB and shared with the rest of the kernel
+const MAX_FILE_SIZE: usize = 1024 * 1024;
+const MAX_TOTAL_SIZE: usize = 4 * 1024 * 1024;
+
+// File system kept in memory, its content is lost on reboot
+enum TmpfsNode{
+    File(Vec<u8>),
+    Folder,
+}
+
+pub struct TmpfsDriver{
+    nodes: BTreeMap<usize, TmpfsNode>,
+    next_id: usize,
+    used: usize, // Heap memory allocated for the content of the files
+}
+
+// Returns an empty file system that can be mounted
+pub fn new_tmpfs() -> MountPoint{
+    let mut driver = TmpfsDriver { nodes: BTreeMap::new(), next_id: 1, used: 0 };
+    driver.nodes.insert(0, TmpfsNode::Folder);
+    MountPoint::new(Inode::new_folder(0), driver)
+}
+
+impl TmpfsDriver{
+    fn allocate_id(&mut self) -> usize{
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn get_file(&self, node: &Inode) -> Result<&Vec<u8>, vfs::Error>{
+        match self.nodes.get(&node.get_id()){
+            Some(TmpfsNode::File(content)) => Ok(content),
+            Some(TmpfsNode::Folder) => Err(vfs::Error::NotAReadableFile),
+            None => Err(vfs::Error::NotFound),
+        }
+    }
+
+    fn get_file_mut(&mut self, node: &Inode) -> Result<&mut Vec<u8>, vfs::Error>{
+        match self.nodes.get_mut(&node.get_id()){
+            Some(TmpfsNode::File(content)) => Ok(content),
+            Some(TmpfsNode::Folder) => Err(vfs::Error::IsAFolder),
+            None => Err(vfs::Error::NotFound),
+        }
+    }
+
+    // Change the size of a file, the new bytes are zeroes. The memory is reserved before
+    // so that a full heap is an error instead of a panic
+    fn resize(&mut self, node: &Inode, size: usize) -> Result<(), vfs::Error>{
+        if size > MAX_FILE_SIZE{
+            return Err(vfs::Error::FileTooBig);
+        }
+        let mut used = self.used;
+        let content = self.get_file_mut(node)?;
+        if size > content.capacity(){
+            if used + (size - content.capacity()) > MAX_TOTAL_SIZE{
+                return Err(vfs::Error::NoSpace);
+            }
+            used -= content.capacity();
+            content.try_reserve_exact(size - content.len()).map_err(|_| vfs::Error::OutOfMemory)?;
+            used += content.capacity();
+        }else if size == 0{
+            // Give the memory back to the heap
+            used -= content.capacity();
+            *content = Vec::new();
+        }
+        content.resize(size, 0);
+        self.used = used;
+        Ok(())
+    }
+}
+
+impl FsDriver for TmpfsDriver{
+    fn get_size(&self, node: &Inode) -> Result<usize, vfs::Error> {
+        match self.nodes.get(&node.get_id()){
+            Some(TmpfsNode::File(content)) => Ok(content.len()),
+            Some(TmpfsNode::Folder) => Ok(0),
+            None => Err(vfs::Error::NotFound),
+        }
+    }
+
+    fn read(&self, node: &Inode, pos: usize, requested_amount: usize) -> Result<Box<[u8]>, vfs::Error> {
+        let content = self.get_file(node)?;
+        let start = pos.min(content.len());
+        let end = start.saturating_add(requested_amount).min(content.len());
+        Ok(Box::from(&content[start..end]))
+    }
+
+    fn write(&mut self, node: &Inode, pos: usize, data: &[u8]) -> Result<usize, vfs::Error> {
+        let end = pos.checked_add(data.len()).ok_or(vfs::Error::FileTooBig)?;
+        if self.get_file_mut(node)?.len() < end{
+            // Writing past the end leaves a hole filled with zeroes
+            self.resize(node, end)?;
+        }
+        self.get_file_mut(node)?[pos..end].copy_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn truncate(&mut self, node: &Inode, size: usize) -> Result<(), vfs::Error> {
+        self.resize(node, size)
+    }
+
+    fn create(&mut self, _parent: &Inode, _name: &str) -> Result<Inode, vfs::Error> {
+        let id = self.allocate_id();
+        self.nodes.insert(id, TmpfsNode::File(Vec::new()));
+        Ok(Inode::new_file(id))
+    }
+
+    fn mkdir(&mut self, _parent: &Inode, _name: &str) -> Result<Inode, vfs::Error> {
+        let id = self.allocate_id();
+        self.nodes.insert(id, TmpfsNode::Folder);
+        Ok(Inode::new_folder(id))
+    }
+
+    fn unlink(&mut self, node: &Inode) -> Result<(), vfs::Error> {
+        if let TmpfsNode::File(content) = self.nodes.remove(&node.get_id()).ok_or(vfs::Error::NotFound)?{
+            self.used -= content.capacity();
+        }
+        Ok(())
+    }
+}
@@ -1,143 +1,339 @@
-use alloc::{boxed::Box, collections::BTreeMap, string::String, vec::Vec};
+use alloc::{boxed::Box, collections::BTreeMap, string::{String, ToString}, vec::Vec};
 
-use super::vfs::{self, FsDriver, Inode, PathBuf};
+use super::{mount, vfs::{self, FsDriver, Inode, Metadata, MountPoint, PathBuf}};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum FileType{
     RegularFile,
     Folder,
+    HardLink,
+    SymLink,
+    GnuLongName, // The data is the name of the next entry
+    GnuLongLinkName,
+    PaxExtended, // The data holds attributes of the next entry
+    PaxGlobal, // The data holds attributes of all the next entries
     Other,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Header{
     name: String,
     size: usize,
     file_type: FileType,
     start_addr: usize,
+    link_name: String, // Target of a link
+    metadata: Metadata,
 }
 
 impl Header {
     pub fn is_readable(&self) -> bool{
-        match self.file_type{
-            FileType::RegularFile => true,
-            _ => false
+        self.file_type == FileType::RegularFile
+    }
+
+    fn is_extension(&self) -> bool{
+        matches!(self.file_type, FileType::GnuLongName | FileType::GnuLongLinkName | FileType::PaxExtended | FileType::PaxGlobal)
+    }
+
+    // Header of a folder that has no entry in the archive
+    fn new_folder() -> Self{
+        Header { name: String::new(), size: 0, file_type: FileType::Folder, start_addr: 0, link_name: String::new(), metadata: Metadata::new_default(true) }
+    }
+
+    // The size of links and folders doesn't count data blocks
+    fn has_data(&self) -> bool{
+        !matches!(self.file_type, FileType::HardLink | FileType::SymLink | FileType::Folder)
+    }
+
+    // Number of bytes taken by the header and the data blocks following it, None if it overflows
+    fn get_record_len(&self) -> Option<usize>{
+        if !self.has_data(){
+            return Some(BLOCK_SIZE);
         }
+        self.size.div_ceil(BLOCK_SIZE).checked_add(1)?.checked_mul(BLOCK_SIZE)
     }
 }
 
+// Attributes of a PAX extended header that replace the ones of the ustar header
+#[derive(Debug, Default, Clone)]
+struct PaxAttributes{
+    path: Option<String>,
+    link_path: Option<String>,
+    size: Option<usize>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    mtime: Option<u64>,
+}
 
-pub fn parse_octal_size(data: &[u8]) -> usize{
-    let mut result = 0;
-    for i in 0..12{
-        if data[i] == 0{
-            return result;
+impl PaxAttributes{
+    // The records are "<length> <key>=<value>\n", length counts the whole record
+    fn parse(&mut self, mut data: &[u8]){
+        while let Some(space) = data.iter().position(|c| *c == b' '){
+            let Some(len) = core::str::from_utf8(&data[..space]).ok().and_then(|len| len.parse::<usize>().ok()) else{
+                return;
+            };
+            if len <= space + 1 || len > data.len(){
+                return;
+            }
+            let record = &data[space + 1..len - 1];
+            data = &data[len..];
+            let Some(equal) = record.iter().position(|c| *c == b'=') else{
+                continue;
+            };
+            let value = String::from_utf8_lossy(&record[equal + 1..]).into_owned();
+            match &record[..equal]{
+                b"path" => self.path = Some(value),
+                b"linkpath" => self.link_path = Some(value),
+                b"size" => self.size = value.parse().ok(),
+                b"uid" => self.uid = value.parse().ok(),
+                b"gid" => self.gid = value.parse().ok(),
+                // The time can have a fractional part
+                b"mtime" => self.mtime = value.split('.').next().and_then(|seconds| seconds.parse().ok()),
+                _ => {}
+            }
+        }
+    }
+
+    fn apply(&self, header: &mut Header){
+        if let Some(path) = &self.path{
+            header.name = path.clone();
+        }
+        if let Some(link_path) = &self.link_path{
+            header.link_name = link_path.clone();
+        }
+        if let Some(size) = self.size{
+            header.size = size;
+        }
+        if let Some(uid) = self.uid{
+            header.metadata.uid = uid;
+        }
+        if let Some(gid) = self.gid{
+            header.metadata.gid = gid;
+        }
+        if let Some(mtime) = self.mtime{
+            header.metadata.mtime = mtime;
         }
-        result *= 8;
-        result += (data[i] - b'0') as usize;
     }
-    result
+}
+
+
+const BLOCK_SIZE: usize = 512;
+
+// The magic of the first header, also found in GNU archives
+pub fn is_ustar(data: &[u8]) -> bool{
+    data.get(257..262) == Some(b"ustar")
+}
+
+// Why an archive can't be loaded, offset is the position of the header in the archive
+#[derive(Debug)]
+pub enum ParseError{
+    Truncated { offset: usize },
+    InvalidMagic { offset: usize },
+    BadChecksum { offset: usize },
+    InvalidNumber { offset: usize, field: &'static str },
+    NotAFolder { path: String }, // An entry is inside something that isn't a folder
+    MissingLinkTarget { path: String }, // A hard link to a file that isn't in the archive
+}
+
+// Numeric fields are octal, padded with leading zeros or spaces and ended by NUL or a space.
+// GNU tar stores the values that don't fit in base 256, with the high bit of the first byte set.
+fn parse_number(data: &[u8]) -> Option<u64>{
+    if data.first().is_some_and(|c| c & 0x80 != 0){
+        let mut result = (data[0] & 0x7f) as u64;
+        for c in data[1..].iter(){
+            result = result.checked_mul(256)?.checked_add(*c as u64)?;
+        }
+        return Some(result);
+    }
+    let mut result: u64 = 0;
+    let mut terminated = false;
+    for c in data.iter().skip_while(|c| **c == b' '){
+        match c{
+            b'0'..=b'7' if !terminated => result = result.checked_mul(8)?.checked_add((c - b'0') as u64)?,
+            b' ' | 0 => terminated = true,
+            _ => return None,
+        }
+    }
+    Some(result)
 }
 
 pub fn parse_name(data: &[u8]) -> String{
     let mut result = String::new();
     let mut i = 0;
-    while i < 100 && data[i] != 0{
+    while i < data.len() && data[i] != 0{
         result.push(data[i] as char);
         i += 1;
     }
     result
 }
 
-pub fn parse_header(data: &[u8], start: usize) -> Option<Header>{
-    let ustart_start = start + 257;
-    if !is_valid_header(&data[ustart_start..ustart_start+5]){
-        return None;
+// The checksum is the sum of the bytes of the header with the checksum field counted as spaces,
+// some old archives summed signed bytes
+fn is_checksum_valid(block: &[u8], checksum: u64) -> bool{
+    let bytes = block.iter().enumerate().map(|(i, c)| if (148..156).contains(&i) { b' ' } else { *c });
+    let unsigned: u64 = bytes.clone().map(|c| c as u64).sum();
+    let signed: i64 = bytes.map(|c| c as i8 as i64).sum();
+    unsigned == checksum || signed == checksum as i64
+}
+
+// None at the end of the archive, which is marked by a zero block or the end of the data
+pub fn parse_header(data: &[u8], start: usize) -> Result<Option<Header>, ParseError>{
+    if start >= data.len(){
+        return Ok(None);
+    }
+    let block = start.checked_add(BLOCK_SIZE).and_then(|end| data.get(start..end)).ok_or(ParseError::Truncated { offset: start })?;
+    if block.iter().all(|c| *c == 0){
+        return Ok(None);
+    }
+    if !is_ustar(block){
+        return Err(ParseError::InvalidMagic { offset: start });
+    }
+    let number = |field: &'static str, range: core::ops::Range<usize>| {
+        parse_number(&block[range]).ok_or(ParseError::InvalidNumber { offset: start, field })
+    };
+    if !is_checksum_valid(block, number("checksum", 148..156)?){
+        return Err(ParseError::BadChecksum { offset: start });
     }
-    let size_start = start + 124;
-    let size = parse_octal_size(&data[size_start..size_start+12]);
-    let file_type = match data[start + 156]{
-        b'0'|0 => FileType::RegularFile,
+    let size = number("size", 124..136)? as usize;
+    let metadata = Metadata {
+        mode: (number("mode", 100..108)? & 0o7777) as u32,
+        uid: number("uid", 108..116)? as u32,
+        gid: number("gid", 116..124)? as u32,
+        mtime: number("mtime", 136..148)?,
+    };
+    let file_type = match block[156]{
+        b'0' | 0 => FileType::RegularFile,
+        b'1' => FileType::HardLink,
+        b'2' => FileType::SymLink,
         b'5' => FileType::Folder,
+        b'L' => FileType::GnuLongName,
+        b'K' => FileType::GnuLongLinkName,
+        b'x' => FileType::PaxExtended,
+        b'g' => FileType::PaxGlobal,
         _ => FileType::Other
     };
-    let name = parse_name(&data[start..start+100]);
-    let start_addr = start + 512;
-    Some(Header { name, size, file_type, start_addr})
-}
-
-pub fn is_valid_header(data: &[u8]) -> bool{
-    let pattern = b"ustar";
-    for i in 0..5{
-        if data[i] != pattern[i]{
-            return false;
-        }
+    let mut name = parse_name(&block[0..100]);
+    // POSIX archives ("ustar\0") can store the start of the path in the prefix field,
+    // the same bytes are used for other fields by the old GNU format ("ustar ")
+    let prefix = parse_name(&block[345..500]);
+    if block[262] == 0 && !prefix.is_empty(){
+        name = prefix + "/" + &name;
     }
-    true
+    let link_name = parse_name(&block[157..257]);
+    let start_addr = start + BLOCK_SIZE;
+    Ok(Some(Header { name, size, file_type, start_addr, link_name, metadata}))
 }
 
-pub fn parse_file(data: &[u8]) -> Vec<Header>{
+// The GNU and PAX extension records are merged in the headers of the entries they describe
+pub fn parse_file(data: &[u8]) -> Result<Vec<Header>, ParseError>{
     let mut pos = 0;
     let mut res = Vec::new();
-    while let Some(header) = parse_header(data, pos){
-        let offset = if header.file_type == FileType::RegularFile && header.size != 0{
-            (((header.size + 511) / 512) + 1) * 512
+    let mut long_name = None;
+    let mut long_link_name = None;
+    let mut pax_attributes = PaxAttributes::default();
+    let mut global_pax_attributes = PaxAttributes::default();
+    while let Some(mut header) = parse_header(data, pos)?{
+        if !header.is_extension(){
+            global_pax_attributes.apply(&mut header);
+            if let Some(name) = long_name.take(){
+                header.name = name;
+            }
+            if let Some(link_name) = long_link_name.take(){
+                header.link_name = link_name;
+            }
+            core::mem::take(&mut pax_attributes).apply(&mut header);
+        }
+        let record_len = header.get_record_len().ok_or(ParseError::Truncated { offset: pos })?;
+        let record = if header.has_data(){
+            header.start_addr.checked_add(header.size).and_then(|end| data.get(header.start_addr..end))
         }else{
-            512
+            Some(&[][..])
         };
-        pos += offset;
-        res.push(header);
+        let record = record.ok_or(ParseError::Truncated { offset: pos })?;
+        pos = pos.checked_add(record_len).ok_or(ParseError::Truncated { offset: pos })?;
+        match header.file_type{
+            FileType::GnuLongName => long_name = Some(parse_name(record)),
+            FileType::GnuLongLinkName => long_link_name = Some(parse_name(record)),
+            FileType::PaxExtended => pax_attributes.parse(record),
+            FileType::PaxGlobal => global_pax_attributes.parse(record),
+            _ => res.push(header),
+        }
     }
-    res
+    Ok(res)
+}
+
+// Folder at path, the folders missing from the archive are created with default metadata
+fn find_or_create_folder<'a>(root: &'a mut Inode, driver: &mut UstarDriver, path: &PathBuf) -> Result<&'a mut Inode, ParseError>{
+    root.find_or_create_folder(path, || driver.add_header(Header::new_folder()))
+        .map_err(|_| ParseError::NotAFolder { path: path.to_string() })
 }
 
-pub fn headers_to_fs(headers: Vec<Header>, data: Box<[u8]>) -> Inode{
+pub fn headers_to_fs(headers: Vec<Header>, data: &'static [u8]) -> Result<MountPoint, ParseError>{
     let mut driver = UstarDriver::new(data);
-    let mut id = 0;
-    let mut root = Inode::new_folder(id);
-    let root_header = Header{ name: String::new(), size: 0, file_type: FileType::Folder, start_addr: 0};
-    driver.insert_header(id, root_header);
-    id += 1;
+    let root_id = driver.add_header(Header::new_folder());
+    let mut root = Inode::new_folder(root_id);
+    let mut hard_links = Vec::new();
     for header in headers.into_iter(){
         let mut path = PathBuf::from(header.name.as_ref());
-        let mut parent = &mut root;
-        if path.is_empty(){
-            panic!("File with no path");
-        }
-        while !path.is_basename(){
-            let component = path.split_first_component().unwrap();
-            parent = parent.search_in_folder_mut(&component).unwrap();
-        }
-
-        let name = path.split_first_component().unwrap();
-
-        match header.file_type{
-            FileType::RegularFile => {
-                let node = Inode::new_file(id);
-                driver.insert_header(id, header);
-                id += 1;
-                parent.add_to_folder(node, name).unwrap();
-            },
+        let Some(name) = path.split_last_component() else{
+            // "./" is the root, which already exists
+            if header.file_type == FileType::Folder{
+                driver.insert_header(root_id, header);
+            }
+            continue;
+        };
+        let parent = find_or_create_folder(&mut root, &mut driver, &path)?;
+        let node = match header.file_type{
+            FileType::RegularFile => Inode::new_file(driver.add_header(header)),
             FileType::Folder => {
-                let node = Inode::new_folder(id);
-                driver.insert_header(id, header);
-                id += 1;
-                parent.add_to_folder(node, name).unwrap();
+                // The folder can already have been created for an earlier entry inside it
+                if let Ok(folder) = parent.search_in_folder(&name) && folder.is_folder(){
+                    driver.insert_header(folder.get_id(), header);
+                    continue;
+                }
+                Inode::new_folder(driver.add_header(header))
             }
-            _ => {}
-        }
+            FileType::SymLink => {
+                let target = header.link_name.clone();
+                Inode::new_symlink(driver.add_header(header), target)
+            }
+            FileType::HardLink => {
+                // The target can come later in the archive
+                hard_links.push(header);
+                continue;
+            }
+            _ => continue,
+        };
+        parent.replace_in_folder(node, name).map_err(|_| ParseError::NotAFolder { path: path.to_string() })?;
+    }
+    for header in hard_links{
+        // The link gets a copy of the header of its target so both names read the same data.
+        // The folders on the path of the target can be symlinks
+        let target = mount::follow_symlinks(PathBuf::from(header.link_name.as_ref()), false, |_| Ok((0, &root)));
+        let mut link = target.and_then(|target| root.find(target)).ok()
+            .and_then(|target| driver.get_header(target.get_id()).ok())
+            .filter(|target| target.is_readable())
+            .cloned()
+            .ok_or_else(|| ParseError::MissingLinkTarget { path: header.name.clone() })?;
+        link.name = header.name;
+        let mut path = PathBuf::from(link.name.as_ref());
+        let Some(name) = path.split_last_component() else{
+            return Err(ParseError::NotAFolder { path: link.name });
+        };
+        let id = driver.add_header(link);
+        let parent = find_or_create_folder(&mut root, &mut driver, &path)?;
+        parent.replace_in_folder(Inode::new_file(id), name).map_err(|_| ParseError::NotAFolder { path: path.to_string() })?;
     }
-    let mount_point = Inode::new_mountpoint(root, driver, 0);
-    mount_point
+    Ok(MountPoint::new(root, driver))
 }
 
+// The archive stays in memory, its files are read in place
 pub struct UstarDriver{
     headers: BTreeMap<usize, Header>,
-    data: Box<[u8]>
+    data: &'static [u8]
 }
 
 impl UstarDriver{
-    pub fn new(data: Box<[u8]>) -> Self{
+    pub fn new(data: &'static [u8]) -> Self{
         UstarDriver { headers: BTreeMap::new(), data }
     }
 
@@ -145,6 +341,13 @@ impl UstarDriver{
         self.headers.insert(id, header);
     }
 
+    // Returns the id of the new header
+    pub fn add_header(&mut self, header: Header) -> usize{
+        let id = self.headers.last_key_value().map_or(0, |(id, _)| id + 1);
+        self.headers.insert(id, header);
+        id
+    }
+
     pub fn get_header(&self, id: usize) -> Result<&Header, vfs::Error>{
         self.headers.get(&id).ok_or(vfs::Error::NotFound)
     }
@@ -157,13 +360,17 @@ impl FsDriver for UstarDriver{
         Ok(header.size)
     }
     
+    fn get_metadata(&self, node: &Inode) -> Result<Metadata, vfs::Error> {
+        Ok(self.get_header(node.get_id())?.metadata)
+    }
+
     fn read(&self, node: &Inode, pos: usize, requested_amount: usize) -> Result<Box<[u8]>, vfs::Error> {
         let id = node.get_id();
         let header = self.get_header(id)?;
         let size = header.size;
         if header.is_readable(){
             let start = pos.min(size);
-            let end = (start + requested_amount).min(size);
+            let end = start.saturating_add(requested_amount).min(size);
             let start_offset = header.start_addr + start;
             let end_offset = header.start_addr + end;
             let data = &self.data[start_offset..end_offset];
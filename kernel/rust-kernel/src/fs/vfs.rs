@@ -1,6 +1,6 @@
-use core::fmt::Debug;
+use core::fmt::{Debug, Display};
 
-use alloc::{boxed::Box, collections::VecDeque, string::String};
+use alloc::{boxed::Box, collections::VecDeque, string::String, vec::Vec};
 use hashbrown::HashMap;
 
 
@@ -8,7 +8,15 @@ use hashbrown::HashMap;
 pub enum InodeType{
     RegularFile,
     Folder(HashMap<String, Inode>),
-    MountPoint(Box<MountPoint>),
+    SymLink(String), // Target, relative to the folder containing the link
+}
+
+// Type of an inode without its content, given with the entries of a folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind{
+    RegularFile,
+    Folder,
+    SymLink,
 }
 
 #[derive(Debug)]
@@ -27,9 +35,8 @@ impl Inode{
         Inode { node_type: InodeType::RegularFile, id: local_id}
     }
 
-    pub fn new_mountpoint<T: FsDriver + 'static>(root: Inode, driver: T, id: usize) -> Self{
-        let mount_point = MountPoint::new(root, driver);
-        Inode { node_type: InodeType::MountPoint(Box::new(mount_point)), id }
+    pub fn new_symlink(local_id: usize, target: String) -> Self{
+        Inode { node_type: InodeType::SymLink(target), id: local_id}
     }
 
     pub fn search_in_folder(&self, name: &str) -> Result<&Inode, Error>{
@@ -37,9 +44,6 @@ impl Inode{
             InodeType::Folder(content) => {
                 content.get(name).ok_or(Error::NotFound)
             },
-            InodeType::MountPoint(mountpoint) => {
-                mountpoint.root.search_in_folder(name)
-            }
             _ => Err(Error::NotAFolder)
         }
     }
@@ -49,14 +53,20 @@ impl Inode{
             InodeType::Folder(content) => {
                 content.get_mut(name).ok_or(Error::NotFound)
             },
-            InodeType::MountPoint(mountpoint) => {
-                mountpoint.root.search_in_folder_mut(name)
-            }
             _ => Err(Error::NotAFolder)
         }
     }
 
 
+    pub fn remove_from_folder(&mut self, name: &str) -> Result<Inode, Error>{
+        match &mut self.node_type{
+            InodeType::Folder(content) => {
+                content.remove(name).ok_or(Error::NotFound)
+            },
+            _ => Err(Error::NotAFolder)
+        }
+    }
+
     pub fn add_to_folder(&mut self, file: Inode, name: String) -> Result<(), Error>{
         match &mut self.node_type{
             InodeType::Folder(content) => {
@@ -66,28 +76,69 @@ impl Inode{
         }
     }
 
-    pub fn get_id(&self) -> usize{
-        self.id
+    // Add the file, an entry with the same name is replaced
+    pub fn replace_in_folder(&mut self, file: Inode, name: String) -> Result<(), Error>{
+        match &mut self.node_type{
+            InodeType::Folder(content) => {
+                content.insert(name, file);
+                Ok(())
+            },
+            _ => Err(Error::NotAFolder)
+        }
     }
 
-    pub fn get_mountpoint(&self) -> Result<&MountPoint, Error>{
-        match &self.node_type{
-            InodeType::MountPoint(mountpoint) => {
-                Ok(mountpoint)
+    // Folder at path, the missing folders are created with ids given by new_folder_id.
+    // Used to build the tree of archives, where folders can be implicit.
+    pub fn find_or_create_folder(&mut self, path: &PathBuf, mut new_folder_id: impl FnMut() -> usize) -> Result<&mut Inode, Error>{
+        let mut node = self;
+        for component in path.get_components(){
+            match node.search_in_folder(component){
+                Ok(child) if !child.is_folder() => return Err(Error::NotAFolder),
+                Ok(_) => {},
+                Err(_) => node.add_to_folder(Inode::new_folder(new_folder_id()), component.clone())?,
             }
-            _ => Err(Error::NotAMountpoint)
+            node = node.search_in_folder_mut(component)?;
         }
+        Ok(node)
     }
 
-    pub fn get_mountpoint_mut(&mut self) -> Result<&mut MountPoint, Error>{
-        match &mut self.node_type{
-            InodeType::MountPoint(mountpoint) => {
-                Ok(mountpoint)
-            }
-            _ => Err(Error::NotAMountpoint)
+    // Content of a folder sorted by name
+    pub fn read_dir(&self) -> Result<Vec<DirEntry>, Error>{
+        match &self.node_type{
+            InodeType::Folder(content) => {
+                let mut entries: Vec<DirEntry> = content.iter().map(|(name, inode)| {
+                    DirEntry { name: name.clone(), kind: inode.get_kind() }
+                }).collect();
+                entries.sort_by(|a, b| a.name.cmp(&b.name));
+                Ok(entries)
+            },
+            _ => Err(Error::NotAFolder)
+        }
+    }
+
+    pub fn is_folder(&self) -> bool{
+        matches!(self.node_type, InodeType::Folder(_))
+    }
+
+    pub fn get_kind(&self) -> FileKind{
+        match self.node_type{
+            InodeType::RegularFile => FileKind::RegularFile,
+            InodeType::Folder(_) => FileKind::Folder,
+            InodeType::SymLink(_) => FileKind::SymLink,
         }
     }
 
+    pub fn get_symlink_target(&self) -> Option<&str>{
+        match &self.node_type{
+            InodeType::SymLink(target) => Some(target),
+            _ => None
+        }
+    }
+
+    pub fn get_id(&self) -> usize{
+        self.id
+    }
+
     pub fn find(&self, mut path: PathBuf) -> Result<&Inode, Error>{
         if let Some(component) = path.split_first_component(){
             let next = self.search_in_folder(&component)?;
@@ -97,12 +148,67 @@ impl Inode{
         }
     }
 
-    pub fn get_size(&self, mountpoint: &MountPoint) -> Result<usize, Error>{
-        mountpoint.driver.get_size(&self)
+    // Index in the path and target of the first symlink on the path, the last component is only
+    // checked if follow_last. The search stops at the first missing component.
+    pub fn find_symlink(&self, path: &PathBuf, follow_last: bool) -> Option<(usize, String)>{
+        let mut node = self;
+        for (i, component) in path.get_components().enumerate(){
+            node = node.search_in_folder(component).ok()?;
+            if let Some(target) = node.get_symlink_target() && (follow_last || i + 1 < path.len()){
+                return Some((i, String::from(target)));
+            }
+        }
+        None
+    }
+
+    pub fn find_mut(&mut self, mut path: PathBuf) -> Result<&mut Inode, Error>{
+        if let Some(component) = path.split_first_component(){
+            let next = self.search_in_folder_mut(&component)?;
+            next.find_mut(path)
+        }else {
+            Ok(self)
+        }
+    }
+}
+
+// Owner, permissions and modification time of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata{
+    pub mode: u32, // Permission bits, 0o755 is rwxr-xr-x
+    pub uid: u32,
+    pub gid: u32,
+    pub mtime: u64, // Seconds since the Unix epoch
+}
+
+impl Metadata{
+    // Used by the drivers that don't store metadata, the files belong to root
+    pub fn new_default(is_folder: bool) -> Self{
+        let mode = if is_folder { 0o755 } else { 0o644 };
+        Metadata { mode, uid: 0, gid: 0, mtime: 0 }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DirEntry{
+    name: String,
+    kind: FileKind,
+}
+
+impl DirEntry{
+    pub fn new(name: String, kind: FileKind) -> Self{
+        DirEntry { name, kind }
+    }
+
+    pub fn get_name(&self) -> &str{
+        &self.name
+    }
+
+    pub fn get_kind(&self) -> FileKind{
+        self.kind
     }
 
-    pub fn read(&self, mountpoint: &MountPoint, node: &Inode, pos: usize, requested_amount: usize) -> Result<Box<[u8]>, Error>{
-        mountpoint.driver.read(node, pos, requested_amount)
+    pub fn is_folder(&self) -> bool{
+        self.kind == FileKind::Folder
     }
 }
 
@@ -113,14 +219,51 @@ pub enum Error{
     NotAFolder,
     NotAMountpoint,
     NotAReadableFile,
+    IsAFolder,
+    NotEmpty,
+    Busy,
+    ReadOnly,
+    FileTooBig,
+    TooManyLinks,
+    Io, // The device holding the file system failed
+    NoSpace, // The file system is full
+    OutOfMemory,
 }
 
-#[derive(Debug, Clone)]
+// Absolute path without "." and "..", the root has no components
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct PathBuf{
     components: VecDeque<String>
 }
 
 impl PathBuf{
+    pub fn new() -> Self{
+        PathBuf { components: VecDeque::new() }
+    }
+
+    // Path of the parent folder, the root is its own parent
+    pub fn parent(&self) -> PathBuf{
+        let mut parent = self.clone();
+        parent.components.pop_back();
+        parent
+    }
+
+    // Path reached by following path from this folder, an absolute path replaces it.
+    // ".." from the root stays at the root like on Unix
+    pub fn join(&self, path: &str) -> PathBuf{
+        let mut res = if path.starts_with('/') { PathBuf::new() } else { self.clone() };
+        for component in path.split('/'){
+            match component{
+                "" | "." => {},
+                ".." => {
+                    res.components.pop_back();
+                },
+                _ => res.components.push_back(String::from(component)),
+            }
+        }
+        res
+    }
+
     pub fn is_empty(&self) -> bool{
         self.components.is_empty()
     }
@@ -129,27 +272,43 @@ impl PathBuf{
         self.components.pop_front()
     }
 
+    pub fn split_last_component(&mut self) -> Option<String>{
+        self.components.pop_back()
+    }
+
+    pub fn push_component(&mut self, component: String){
+        self.components.push_back(component);
+    }
+
     pub fn is_basename(&self) -> bool{
         self.components.len() == 1
     }
+
+    pub fn len(&self) -> usize{
+        self.components.len()
+    }
+
+    pub fn get_components(&self) -> impl Iterator<Item = &String>{
+        self.components.iter()
+    }
 }
 
+// Relative paths start from the root
 impl From<&str> for PathBuf{
     fn from(value: &str) -> Self {
-        let mut components = VecDeque::new();
-        let mut current_components = String::new();
-        for c in value.chars(){
-            if c == '/' && !current_components.is_empty(){
-                components.push_back(current_components);
-                current_components = String::new();
-            }else{
-                current_components.push(c);
-            }
+        PathBuf::new().join(value)
+    }
+}
+
+impl Display for PathBuf{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.components.is_empty(){
+            return write!(f, "/");
         }
-        if !current_components.is_empty(){
-            components.push_back(current_components);
+        for component in self.components.iter(){
+            write!(f, "/{}", component)?;
         }
-        PathBuf { components }
+        Ok(())
     }
 }
 
@@ -163,6 +322,83 @@ impl MountPoint{
     pub fn new<T: FsDriver + 'static>(root: Inode, driver: T) -> Self{
         MountPoint { root, driver: Box::new(driver) }
     }
+
+    pub fn get_root(&self) -> &Inode{
+        &self.root
+    }
+
+    pub fn get_driver(&self) -> &dyn FsDriver{
+        self.driver.as_ref()
+    }
+
+    // The paths below are relative to the root of the mount point
+
+    pub fn get_size(&self, path: PathBuf) -> Result<usize, Error>{
+        let node = self.root.find(path)?;
+        if node.is_folder(){
+            return Ok(0);
+        }
+        self.driver.get_size(node)
+    }
+
+    pub fn get_metadata(&self, path: PathBuf) -> Result<Metadata, Error>{
+        let node = self.root.find(path)?;
+        self.driver.get_metadata(node)
+    }
+
+    pub fn read(&self, path: PathBuf, pos: usize, requested_amount: usize) -> Result<Box<[u8]>, Error>{
+        let node = self.root.find(path)?;
+        self.driver.read(node, pos, requested_amount)
+    }
+
+    pub fn read_dir(&self, path: PathBuf) -> Result<Vec<DirEntry>, Error>{
+        self.root.find(path)?.read_dir()
+    }
+
+    pub fn write(&mut self, path: PathBuf, pos: usize, data: &[u8]) -> Result<usize, Error>{
+        let node = self.root.find(path)?;
+        if node.is_folder(){
+            return Err(Error::IsAFolder);
+        }
+        self.driver.write(node, pos, data)
+    }
+
+    pub fn truncate(&mut self, path: PathBuf, size: usize) -> Result<(), Error>{
+        let node = self.root.find(path)?;
+        if node.is_folder(){
+            return Err(Error::IsAFolder);
+        }
+        self.driver.truncate(node, size)
+    }
+
+    // Create an empty file or folder named name in the folder at parent
+    pub fn create(&mut self, parent: PathBuf, name: String, folder: bool) -> Result<(), Error>{
+        let parent = self.root.find_mut(parent)?;
+        if !parent.is_folder(){
+            return Err(Error::NotAFolder);
+        }
+        if parent.search_in_folder(&name).is_ok(){
+            return Err(Error::FileAlreadyExist);
+        }
+        let node = if folder{
+            self.driver.mkdir(parent, &name)?
+        }else{
+            self.driver.create(parent, &name)?
+        };
+        parent.add_to_folder(node, name)
+    }
+
+    // Remove a file or an empty folder
+    pub fn unlink(&mut self, parent: PathBuf, name: &str) -> Result<(), Error>{
+        let parent = self.root.find_mut(parent)?;
+        let node = parent.search_in_folder(name)?;
+        if let InodeType::Folder(content) = &node.node_type && !content.is_empty(){
+            return Err(Error::NotEmpty);
+        }
+        self.driver.unlink(node)?;
+        parent.remove_from_folder(name)?;
+        Ok(())
+    }
 }
 
 impl Debug for MountPoint{
@@ -171,7 +407,36 @@ impl Debug for MountPoint{
     }
 }
 
-pub trait FsDriver {
+// The file tree is kept by the VFS, the driver only stores the content of the inodes.
+// The operations that modify a file system fail with ReadOnly unless the driver implements them.
+pub trait FsDriver: Send + Sync {
     fn get_size(&self, node: &Inode) -> Result<usize, Error>;
     fn read(&self, node: &Inode, pos: usize, requested_amount: usize) -> Result<Box<[u8]>, Error>;
+
+    fn get_metadata(&self, node: &Inode) -> Result<Metadata, Error>{
+        Ok(Metadata::new_default(node.is_folder()))
+    }
+
+    // Returns the number of bytes written, the file grows if needed
+    fn write(&mut self, _node: &Inode, _pos: usize, _data: &[u8]) -> Result<usize, Error>{
+        Err(Error::ReadOnly)
+    }
+
+    fn truncate(&mut self, _node: &Inode, _size: usize) -> Result<(), Error>{
+        Err(Error::ReadOnly)
+    }
+
+    // Returns the inode of the new file, the VFS adds it to the parent
+    fn create(&mut self, _parent: &Inode, _name: &str) -> Result<Inode, Error>{
+        Err(Error::ReadOnly)
+    }
+
+    fn mkdir(&mut self, _parent: &Inode, _name: &str) -> Result<Inode, Error>{
+        Err(Error::ReadOnly)
+    }
+
+    // Called before the VFS removes the inode from its parent
+    fn unlink(&mut self, _node: &Inode) -> Result<(), Error>{
+        Err(Error::ReadOnly)
+    }
 }
\ No newline at end of file
@@ -3,7 +3,7 @@ use core::mem::MaybeUninit;
 use alloc::collections::vec_deque::VecDeque;
 use spin::Mutex;
 
-use crate::{PIC_sendEOI, apic, inb, io_wait, keyboard_interrupt, kputc, outb};
+use crate::{PIC_sendEOI, apic, inb, io_wait, keyboard_interrupt, kputc, outb, scheduler::wait_queue::WaitQueue};
 
 const KEYBOARD_DATA_PORT: u16 = 0x60;
 const KEYBOARD_COMMAND_PORT: u16 = 0x64;
@@ -83,6 +83,7 @@ pub fn handle_keyboard_interrupt() {
 pub fn handle_apic_keyboard_interrupt() {
     let scancode = unsafe { read_scancode() };
     push_input(scancode);
+    KEYBOARD_WAIT_QUEUE.wake_all();
     apic::send_EOI();
 }
 
@@ -238,6 +239,9 @@ pub fn init() {
     KEYBOARD_BUFFER.lock().write(VecDeque::new());
 }
 
+// Processes waiting for an input
+pub static KEYBOARD_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+
 // This should only be accessed by the bootstrap processor
 static KEYBOARD_BUFFER: Mutex<MaybeUninit<VecDeque<u8>>> = Mutex::new(MaybeUninit::uninit());
 
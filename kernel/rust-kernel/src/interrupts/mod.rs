@@ -1,4 +1,4 @@
-use crate::{apic, find_page_entry, pit, println};
+use crate::{apic, find_page_entry, percpu, pit, println, scheduler::get_scheduler};
 
 pub mod pic;
 pub use pic::*;
@@ -14,19 +14,66 @@ pub const apic_keyboard: u8 = 49;
 pub const PIT_APIC: u8 = 48;
 pub const APIC_TIMER: u8 = 50;
 pub const division_by_0: u8 = 0;
+pub const general_protection_fault: u8 = 13;
+pub const syscall_interrupt: u8 = 64;
 
+// Exit codes of the processes ended by a fault, 128 + the signal number like in a Unix shell
+const SIGFPE_EXIT_CODE: u64 = 128 + 8;
+const SIGSEGV_EXIT_CODE: u64 = 128 + 11;
+
+// Layout of the stack built by common_interrupt_handler in interrupts.asm
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InterruptFrame{
+    pub es: u64,
+    pub ds: u64,
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub rbp: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rbx: u64,
+    pub rax: u64,
+    pub interrupt_code: u64,
+    pub error_code: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+impl InterruptFrame{
+    pub fn from_user_mode(&self) -> bool{
+        (self.cs & 3) == 3
+    }
+}
+
+// Called by interrupts.c with the frame on the stack of the interrupt, never null
 #[unsafe(no_mangle)]
-pub extern "C" fn rust_interrupt_handler(interrupt_code: u64, error_code: u64){
+pub extern "C" fn rust_interrupt_handler(frame: &mut InterruptFrame){
+    let interrupt_code = frame.interrupt_code;
+    let error_code = frame.error_code;
     match interrupt_code as u8{
         keyboard_interrupt => {keyboard::handle_keyboard_interrupt();},
         double_fault => {double_fault_handler(error_code);},
         PIT_Interrupt => {PIT_handler();},
-        page_fault => {page_fault_handler(error_code);},
+        page_fault => {page_fault_handler(frame);},
         first_pic_spurious => {PIC_sendEOI(first_pic_spurious);},
         apic_keyboard => {keyboard::handle_apic_keyboard_interrupt()},
         PIT_APIC => {pit::interrupt_apic()},
-        APIC_TIMER => {handle_apic_timer();}
-        division_by_0 => {panic!("Division by 0")},
+        APIC_TIMER => {handle_apic_timer(frame);}
+        syscall_interrupt => {syscall::syscall_handler(frame);}
+        division_by_0 => {fault_handler(frame, "Division by 0", SIGFPE_EXIT_CODE)},
+        general_protection_fault => {fault_handler(frame, "General protection fault", SIGSEGV_EXIT_CODE)},
         _ => {println!("Unhandled interrupt {}, error code: {}. Ignoring it.", interrupt_code, error_code);},
     }
 }
@@ -39,8 +86,23 @@ pub fn PIT_handler(){
     PIC_sendEOI(PIT_Interrupt);
 }
 
-pub fn page_fault_handler(error_code: u64){
+// A fault in a user program ends the process, in the kernel it is a bug
+pub fn fault_handler(frame: &mut InterruptFrame, name: &str, exit_code: u64){
+    if !frame.from_user_mode(){
+        panic!("{} in the kernel at 0x{:x}", name, frame.rip);
+    }
+    println!("{} at 0x{:x}, ending the process", name, frame.rip);
+    syscall::syscall_exit(exit_code, frame);
+}
+
+pub fn page_fault_handler(frame: &mut InterruptFrame){
     let cr2 = x86_64::registers::control::Cr2::read_raw();
+    if frame.from_user_mode(){
+        println!("Page fault at address 0x{:x}, ending the process", cr2);
+        syscall::syscall_exit(SIGSEGV_EXIT_CODE, frame);
+        return;
+    }
+    let error_code = frame.error_code;
     println!("Page fault at address: 0x{:x}", cr2);
     let (frame, cr3) = x86_64::registers::control::Cr3::read_raw();
     println!("cr3: 0x{:x}, frame: {:?}", cr3, frame);
@@ -53,7 +115,16 @@ pub fn page_fault_handler(error_code: u64){
     panic!("Not able to recover (yet).");
 }
 
-pub fn handle_apic_timer(){
+pub fn handle_apic_timer(frame: &mut InterruptFrame){
     apic::send_EOI();
+    let mut scheduler_ref = get_scheduler();
+    // Every core has a timer but only the bootstrap processor counts the ticks
+    if percpu::get().get_cpu_id() == 0{
+        scheduler_ref.tick();
+    }
+    // The kernel isn't preemptible, only switch process if the timer interrupted user code
+    if frame.from_user_mode(){
+        scheduler_ref.schedule(frame);
+    }
 }
 
@@ -1,100 +1,503 @@
 use core::alloc::Layout;
 
-use crate::{keyboard, kputc, kputs, scheduler};
-
-#[unsafe(no_mangle)]
-pub extern "C" fn syscall_handler(
-    rdi: u64,
-    rsi: u64,
-    rdx: u64,
-    _rcx: u64,
-    _r8: u64,
-    _r9: u64,
-) -> u64 {
-    let mut rax = 0;
-    match rdi {
+use alloc::{ffi::CString, string::{String, ToString}, vec::Vec};
+use zerocopy::{Immutable, IntoBytes};
+use x86_64::{
+    VirtAddr,
+    registers::{
+        model_specific::{Efer, EferFlags, LStar, SFMask, Star},
+        rflags::RFlags,
+        segmentation::SegmentSelector,
+    },
+};
+
+use crate::{apic::timer::TICK_MS, errno::{self, Errno, SyscallResult}, fs::{self, vfs, file::{OpenFile, O_ACCMODE, O_CREAT, O_TRUNC, O_VALID_FLAGS}}, interrupts::InterruptFrame, keyboard, kputc, kputs, uaccess, scheduler::{address_space::AddressSpace, get_scheduler, process::Process, switch_to_next_process, WaitResult, CHILD_EXIT_QUEUE}, syscall_entry};
+
+const SYSCALL_INSTRUCTION_SIZE: u64 = 2; // int 0x40 and syscall have the same size
+
+const PATH_MAX: usize = 4096;
+
+const SEEK_SET: u64 = 0;
+const SEEK_CUR: u64 = 1;
+const SEEK_END: u64 = 2;
+const PRINT_MAX: usize = 64 * 1024;
+
+const KERNEL_CODE_SEGMENT: u16 = 0x28;
+const KERNEL_DATA_SEGMENT: u16 = 0x30;
+const USER_DATA_SEGMENT: u16 = 0x3B;
+const USER_CODE_SEGMENT: u16 = 0x43;
+
+// Enable the syscall instruction on the current core, int 0x40 keeps working
+pub fn init_syscall_instruction(){
+    Star::write(
+        SegmentSelector(USER_CODE_SEGMENT),
+        SegmentSelector(USER_DATA_SEGMENT),
+        SegmentSelector(KERNEL_CODE_SEGMENT),
+        SegmentSelector(KERNEL_DATA_SEGMENT),
+    ).unwrap();
+    LStar::write(VirtAddr::new(syscall_entry as *const () as u64));
+    // syscall_entry runs with interrupts disabled until it is on the kernel stack
+    SFMask::write(RFlags::INTERRUPT_FLAG | RFlags::DIRECTION_FLAG | RFlags::TRAP_FLAG | RFlags::ALIGNMENT_CHECK);
+    unsafe { Efer::update(|flags| flags.insert(EferFlags::SYSTEM_CALL_EXTENSIONS)) };
+}
+
+// Syscalls that don't return to the caller or block write rax themselves,
+// the others return a value or an error that is written in rax.
+pub fn syscall_handler(frame: &mut InterruptFrame) {
+    let rdi = frame.rdi;
+    let rsi = frame.rsi;
+    let rdx = frame.rdx;
+    let r10 = frame.r10; // rcx is used by the syscall instruction
+    let result = match rdi {
         1 => {
-            unsafe { kputs(rsi as *mut i8) };
+            syscall_print(rsi)
         }
         2 => {
-            syscall_exit(rsi);
+            // Switch to another process, the frame must not be modified after this
+            syscall_exit(rsi, frame);
+            return;
         }
         3 => {
-            syscall_read_keyboard(&mut rax);
+            // May block until a key is pressed
+            syscall_read_keyboard(frame);
+            return;
         },
         4 => {
-            syscall_alloc(rsi, rdx, &mut rax);
+            syscall_alloc(rsi, rdx)
         },
         5 => {
-            syscall_free(rsi);
+            syscall_free(rsi)
         },
         6 => {
-            file_syscall_handler();
+            syscall_open(rsi, rdx)
         }
         7 => {
-            move_cursor_syscall_handler(rsi, rdx);
+            move_cursor_syscall_handler(rsi, rdx)
         }
         8 => {
             unsafe{
                 kputc(rsi as i8);
             }
+            Ok(0)
+        }
+        9 => {
+            syscall_spawn(rsi)
+        }
+        10 => {
+            // The frame is replaced on success
+            match syscall_exec(rsi, frame){
+                Ok(()) => return,
+                Err(errno) => Err(errno),
+            }
+        }
+        11 => {
+            syscall_fork(frame)
+        }
+        12 => {
+            // May block until the child exits
+            syscall_waitpid(rsi, frame);
+            return;
+        }
+        13 => {
+            syscall_sleep(rsi, frame);
+            return;
+        }
+        14 => {
+            syscall_read(rsi, rdx, r10)
+        }
+        15 => {
+            syscall_close(rsi)
+        }
+        16 => {
+            syscall_lseek(rsi, rdx, r10)
+        }
+        17 => {
+            syscall_fstat(rsi, rdx)
+        }
+        18 => {
+            syscall_getdents(rsi, rdx, r10)
+        }
+        19 => {
+            syscall_write(rsi, rdx, r10)
+        }
+        20 => {
+            syscall_mkdir(rsi)
+        }
+        21 => {
+            syscall_unlink(rsi)
+        }
+        22 => {
+            syscall_ftruncate(rsi, rdx)
+        }
+        23 => {
+            syscall_mount(rsi, rdx)
+        }
+        24 => {
+            syscall_umount(rsi)
+        }
+        25 => {
+            syscall_chdir(rsi)
+        }
+        26 => {
+            syscall_getcwd(rsi, rdx)
         }
         _ => {
-            panic!("Unknown syscall {}", rdi);
+            Err(Errno::ENOSYS)
         }
-    }
-    rax
+    };
+    frame.rax = errno::to_return_value(result);
+}
+
+// Absolute path, a relative path starts from the working folder of the process
+fn read_user_path(ptr: u64) -> Result<String, Errno>{
+    let path = uaccess::strncpy_from_user(ptr, PATH_MAX)?;
+    let path = String::from_utf8_lossy(&path);
+    Ok(with_current_process(|process| process.get_cwd().join(&path)).to_string())
 }
 
-pub fn syscall_exit(_exit_code: u64) {
+pub fn syscall_print(ptr: u64) -> SyscallResult{
+    let string = uaccess::strncpy_from_user(ptr, PRINT_MAX).map_err(|errno| {
+        if errno == Errno::ENAMETOOLONG { Errno::EINVAL } else { errno }
+    })?;
+    // The copy has no null byte inside
+    let string = CString::new(string).unwrap();
+    unsafe { kputs(string.as_ptr() as *mut i8) };
+    Ok(0)
+}
+
+pub fn syscall_exit(exit_code: u64, frame: &mut InterruptFrame) {
     x86_64::instructions::interrupts::without_interrupts(|| unsafe {
-        scheduler
-            .get()
-            .as_mut()
-            .unwrap()
-            .assume_init_mut()
-            .end_current_process();
+        get_scheduler().end_current_process(exit_code);
+        CHILD_EXIT_QUEUE.wake_all();
+        switch_to_next_process(frame);
     });
 }
 
-pub fn syscall_read_keyboard(rax: &mut u64) {
+// Blocks until an input is available, the syscall is restarted when a key is pressed
+pub fn syscall_read_keyboard(frame: &mut InterruptFrame) {
     x86_64::instructions::interrupts::without_interrupts(|| {
+        let scheduler_ref = get_scheduler();
         if let Some(input) = keyboard::pop_input() {
-            *rax = input as u64;
+            frame.rax = input as u64;
         } else {
-            *rax = 0;
+            frame.rip -= SYSCALL_INSTRUCTION_SIZE;
+            keyboard::KEYBOARD_WAIT_QUEUE.sleep(scheduler_ref, frame);
         }
     });
 }
 
-pub fn syscall_alloc(size: u64, align: u64, out: &mut u64){
+pub fn syscall_alloc(size: u64, align: u64) -> SyscallResult{
+    let layout = Layout::from_size_align(size as usize, align as usize).map_err(|_| Errno::EINVAL)?;
     x86_64::instructions::interrupts::without_interrupts(|| {
-        let layout = Layout::from_size_align(size as usize, align as usize).unwrap();
-        let process = unsafe { scheduler.get().as_mut().unwrap().assume_init_mut()
-            .get_current_process()
-            .unwrap()
-        };
-        *out = process.malloc(layout) as u64;
+        let scheduler_ref = get_scheduler();
+        let process = scheduler_ref.get_current_process().unwrap();
+        match process.malloc(layout){
+            0 => Err(Errno::ENOMEM),
+            ptr => Ok(ptr as u64),
+        }
     })
 }
 
-pub fn syscall_free(ptr: u64){
+pub fn syscall_free(ptr: u64) -> SyscallResult{
     x86_64::instructions::interrupts::without_interrupts(||{
-        let process = unsafe {
-            scheduler.get().as_mut().unwrap().assume_init_mut()
-            .get_current_process()
-            .unwrap()
-        };
-        process.free(ptr);
+        let scheduler_ref = get_scheduler();
+        let process = scheduler_ref.get_current_process().unwrap();
+        process.free(ptr)?;
+        Ok(0)
     })
 }
 
-pub fn file_syscall_handler(){
+// Start the program at path in a new process and return its pid
+pub fn syscall_spawn(path: u64) -> SyscallResult{
+    let path = read_user_path(path)?;
+    let data = fs::read_file(&path)?;
+    let (pid, parent_pid, cwd) = x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut scheduler_ref = get_scheduler();
+        let parent = scheduler_ref.get_current_process().unwrap();
+        let (parent_pid, cwd) = (parent.get_pid(), parent.get_cwd().clone());
+        (scheduler_ref.allocate_pid(), parent_pid, cwd)
+    });
+    // Loading the program doesn't need the scheduler lock
+    let mut process = Process::from_elf(pid, &data)?;
+    process.set_cwd(cwd);
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_scheduler().add_process(process, parent_pid);
+    });
+    Ok(pid as u64)
+}
 
+// Replace the current program, the frame is only modified on success
+pub fn syscall_exec(path: u64, frame: &mut InterruptFrame) -> Result<(), Errno>{
+    let path = read_user_path(path)?;
+    let data = fs::read_file(&path)?;
+    let pid = x86_64::instructions::interrupts::without_interrupts(|| get_scheduler().get_current_process().unwrap().get_pid());
+    // Loading the program doesn't need the scheduler lock
+    let new_process = Process::from_elf(pid, &data)?;
+    let old_process = x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut scheduler_ref = get_scheduler();
+        let process = scheduler_ref.get_current_process_mut().unwrap();
+        let old_process = process.exec(new_process);
+        process.restore_context(frame);
+        unsafe { process.get_address_space().activate() };
+        old_process
+    });
+    // Its address space isn't active anymore, the pages are freed without the lock
+    drop(old_process);
+    Ok(())
 }
 
-pub fn move_cursor_syscall_handler(x: u64, y: u64){
+// Returns the pid of the child in the parent and 0 in the child
+pub fn syscall_fork(frame: &InterruptFrame) -> SyscallResult{
+    let parent_pml4 = x86_64::instructions::interrupts::without_interrupts(|| {
+        get_scheduler().get_current_process().unwrap().get_address_space().get_pml4()
+    });
+    // The parent is blocked in this syscall so its memory doesn't change during the copy,
+    // which is done without the scheduler lock
+    let address_space = unsafe { AddressSpace::try_clone_from(parent_pml4) }.ok_or(Errno::ENOMEM)?;
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut scheduler_ref = get_scheduler();
+        let pid = scheduler_ref.allocate_pid();
+        let parent = scheduler_ref.get_current_process().unwrap();
+        let parent_pid = parent.get_pid();
+        let child = parent.fork(pid, frame, address_space);
+        scheduler_ref.add_process(child, parent_pid);
+        Ok(pid as u64)
+    })
+}
+
+// Returns the exit code of the child, if it is still running the syscall
+// is restarted when a process exits
+pub fn syscall_waitpid(pid: u64, frame: &mut InterruptFrame){
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut scheduler_ref = get_scheduler();
+        match scheduler_ref.try_wait(pid as usize){
+            WaitResult::Exited(exit_code) => {
+                frame.rax = exit_code;
+            },
+            WaitResult::NoChild => {
+                frame.rax = errno::to_return_value(Err(Errno::ECHILD));
+            },
+            WaitResult::Running => {
+                frame.rip -= SYSCALL_INSTRUCTION_SIZE;
+                CHILD_EXIT_QUEUE.sleep(scheduler_ref, frame);
+            }
+        }
+    })
+}
+
+pub fn syscall_sleep(ms: u64, frame: &mut InterruptFrame){
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        frame.rax = 0;
+        get_scheduler().sleep_current_process(ms.div_ceil(TICK_MS), frame);
+        switch_to_next_process(frame);
+    })
+}
+
+// Run f on the current process with the scheduler locked
+fn with_current_process<T>(f: impl FnOnce(&mut Process) -> T) -> T{
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut scheduler_ref = get_scheduler();
+        f(scheduler_ref.get_current_process_mut().unwrap())
+    })
+}
+
+// O_CREAT creates a missing file, O_TRUNC empties the file if it is opened for writing
+pub fn syscall_open(path: u64, flags: u64) -> SyscallResult{
+    let path = read_user_path(path)?;
+    if flags & !O_VALID_FLAGS != 0 || flags & O_ACCMODE == O_ACCMODE{
+        return Err(Errno::EINVAL);
+    }
+    let file = OpenFile::new(path, flags);
+    match fs::stat(file.get_path()){
+        Ok(stat) => {
+            if stat.file_type == fs::FILE_TYPE_FOLDER && file.can_write(){
+                return Err(Errno::EISDIR);
+            }
+            if flags & O_TRUNC != 0 && file.can_write(){
+                fs::truncate(file.get_path(), 0)?;
+            }
+        },
+        Err(vfs::Error::NotFound) if flags & O_CREAT != 0 => {
+            fs::create_file(file.get_path())?;
+        },
+        Err(error) => return Err(error.into()),
+    }
+    let fd = with_current_process(|process| process.get_files_mut().insert(file))?;
+    Ok(fd as u64)
+}
+
+// Returns the number of bytes read, 0 at the end of the file
+pub fn syscall_read(fd: u64, buffer: u64, len: u64) -> SyscallResult{
+    let file = with_current_process(|process| process.get_files().get(fd as usize).cloned())?;
+    if !file.can_read(){
+        return Err(Errno::EBADF);
+    }
+    // The file system isn't accessed with the scheduler locked
+    let data = fs::read(file.get_path(), file.get_offset(), len as usize)?;
+    uaccess::copy_to_user(buffer, &data)?;
+    with_current_process(|process| {
+        let file = process.get_files_mut().get_mut(fd as usize)?;
+        file.set_offset(file.get_offset() + data.len());
+        Ok(data.len() as u64)
+    })
+}
+
+// Returns the number of bytes written, with O_APPEND the data is written at the end of the file
+pub fn syscall_write(fd: u64, buffer: u64, len: u64) -> SyscallResult{
+    let file = with_current_process(|process| process.get_files().get(fd as usize).cloned())?;
+    if !file.can_write(){
+        return Err(Errno::EBADF);
+    }
+    let data = uaccess::copy_buffer_from_user(buffer, len as usize)?;
+    let pos = if file.is_append(){
+        fs::stat(file.get_path())?.size as usize
+    }else{
+        file.get_offset()
+    };
+    let written = fs::write(file.get_path(), pos, &data)?;
+    with_current_process(|process| {
+        process.get_files_mut().get_mut(fd as usize)?.set_offset(pos + written);
+        Ok(written as u64)
+    })
+}
+
+pub fn syscall_mkdir(path: u64) -> SyscallResult{
+    let path = read_user_path(path)?;
+    fs::mkdir(&path)?;
+    Ok(0)
+}
+
+// Remove a file or an empty folder
+pub fn syscall_unlink(path: u64) -> SyscallResult{
+    let path = read_user_path(path)?;
+    fs::unlink(&path)?;
+    Ok(0)
+}
+
+pub fn syscall_ftruncate(fd: u64, size: u64) -> SyscallResult{
+    let file = with_current_process(|process| process.get_files().get(fd as usize).cloned())?;
+    if !file.can_write(){
+        return Err(Errno::EBADF);
+    }
+    fs::truncate(file.get_path(), size as usize)?;
+    Ok(0)
+}
+
+// Mount a new file system of type fs_type on the target folder
+pub fn syscall_mount(target: u64, fs_type: u64) -> SyscallResult{
+    let target = read_user_path(target)?;
+    // The type is a name, not a path
+    let fs_type = uaccess::strncpy_from_user(fs_type, PATH_MAX)?;
+    let filesystem = match fs_type.as_slice(){
+        b"tmpfs" => fs::tmpfs::new_tmpfs(),
+        _ => return Err(Errno::ENODEV),
+    };
+    fs::mount(&target, filesystem)?;
+    Ok(0)
+}
+
+// Open files keep their path, so they refer to the parent file system afterwards
+pub fn syscall_umount(target: u64) -> SyscallResult{
+    let target = read_user_path(target)?;
+    fs::umount(&target)?;
+    Ok(0)
+}
+
+pub fn syscall_chdir(path: u64) -> SyscallResult{
+    let path = read_user_path(path)?;
+    if fs::stat(&path)?.file_type != fs::FILE_TYPE_FOLDER{
+        return Err(Errno::ENOTDIR);
+    }
+    with_current_process(|process| process.set_cwd(vfs::PathBuf::from(path.as_str())));
+    Ok(0)
+}
+
+// Write the null terminated working folder in the buffer and return its length without the terminator
+pub fn syscall_getcwd(buffer: u64, len: u64) -> SyscallResult{
+    let mut cwd = with_current_process(|process| process.get_cwd().to_string()).into_bytes();
+    cwd.push(0);
+    if cwd.len() > len as usize{
+        return Err(Errno::ERANGE);
+    }
+    uaccess::copy_to_user(buffer, &cwd)?;
+    Ok(cwd.len() as u64 - 1)
+}
+
+pub fn syscall_close(fd: u64) -> SyscallResult{
+    with_current_process(|process| process.get_files_mut().remove(fd as usize))?;
+    Ok(0)
+}
+
+// Returns the new offset, it can be past the end of the file
+pub fn syscall_lseek(fd: u64, offset: u64, whence: u64) -> SyscallResult{
+    let file = with_current_process(|process| process.get_files().get(fd as usize).cloned())?;
+    let base = match whence{
+        SEEK_SET => 0,
+        SEEK_CUR => file.get_offset() as i64,
+        SEEK_END => fs::stat(file.get_path())?.size as i64,
+        _ => return Err(Errno::EINVAL),
+    };
+    let new_offset = base.checked_add(offset as i64).filter(|offset| *offset >= 0).ok_or(Errno::EINVAL)?;
+    with_current_process(|process| {
+        process.get_files_mut().get_mut(fd as usize)?.set_offset(new_offset as usize);
+        Ok(new_offset as u64)
+    })
+}
+
+// Header of a record written by getdents, see struct dirent in library/src/lib.h.
+// It is followed by the null terminated name, records are aligned on 8 bytes.
+#[repr(C)]
+#[derive(IntoBytes, Immutable)]
+struct DirentHeader{
+    reclen: u32,
+    file_type: u32,
+}
+
+// Fill the buffer with the next entries of the folder and return the number of bytes written,
+// 0 once all the entries have been read. The offset of the file is the index of the next entry.
+pub fn syscall_getdents(fd: u64, buffer: u64, len: u64) -> SyscallResult{
+    let file = with_current_process(|process| process.get_files().get(fd as usize).cloned())?;
+    let entries = fs::read_dir(file.get_path())?;
+    let mut records = Vec::new();
+    let mut index = file.get_offset();
+    while let Some(entry) = entries.get(index){
+        let name = entry.get_name().as_bytes();
+        let reclen = (size_of::<DirentHeader>() + name.len() + 1).next_multiple_of(8);
+        if records.len() + reclen > len as usize{
+            break;
+        }
+        let file_type = fs::get_file_type(entry.get_kind());
+        let header = DirentHeader { reclen: reclen as u32, file_type: file_type as u32 };
+        let start = records.len();
+        records.extend_from_slice(header.as_bytes());
+        records.extend_from_slice(name);
+        records.resize(start + reclen, 0);
+        index += 1;
+    }
+    if records.is_empty() && index < entries.len(){
+        // The buffer can't hold the next entry
+        return Err(Errno::EINVAL);
+    }
+    uaccess::copy_to_user(buffer, &records)?;
+    with_current_process(|process| {
+        process.get_files_mut().get_mut(fd as usize)?.set_offset(index);
+        Ok(records.len() as u64)
+    })
+}
+
+pub fn syscall_fstat(fd: u64, stat: u64) -> SyscallResult{
+    let file = with_current_process(|process| process.get_files().get(fd as usize).cloned())?;
+    let metadata = fs::stat(file.get_path())?;
+    uaccess::copy_to_user(stat, metadata.as_bytes())?;
+    Ok(0)
+}
+
+pub fn move_cursor_syscall_handler(x: u64, y: u64) -> SyscallResult{
     unsafe{
+        if x >= crate::char_per_row as u64 || y >= crate::rows_count as u64{
+            return Err(Errno::EINVAL);
+        }
         crate::move_cursor(x as usize, y as usize);
     }
-}
\ No newline at end of file
+    Ok(0)
+}
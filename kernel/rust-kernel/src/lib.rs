@@ -1,8 +1,6 @@
 #![no_std]
 #![no_main]
 
-#![feature(sync_unsafe_cell)]
-
 #![allow(non_upper_case_globals)]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
@@ -11,17 +9,19 @@ include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
 extern crate alloc;
 
-use core::{cell::SyncUnsafeCell, ffi::c_int, mem::MaybeUninit, panic::PanicInfo, slice};
+use alloc::{string::{String, ToString}, vec::Vec};
+use core::{ffi::{c_char, c_int, CStr}, panic::PanicInfo, slice};
 pub mod interrupts;
-use alloc::boxed::Box;
-use fs::vfs;
 pub use interrupts::*;
 use rsdt::MADT;
 use x86_64::instructions::hlt;
 
+use crate::cmdline::{Console, LogLevel};
+use crate::fs::vfs::{MountPoint, PathBuf};
 use crate::scheduler::{process::Process, Scheduler};
 
 pub mod fs;
+pub mod block;
 pub mod pci;
 pub mod pit;
 pub mod cpuid;
@@ -31,6 +31,12 @@ pub mod elf;
 pub mod scheduler;
 pub mod allocator;
 pub mod print;
+pub mod percpu;
+pub mod errno;
+pub mod uaccess;
+pub mod cmdline;
+pub mod serial;
+pub mod compression;
 
 
 
@@ -38,7 +44,7 @@ const PTE_PRESENT: c_int = 1;
 const PTE_READ_WRITE: c_int = 2;
 const PTE_USER_SUPERVISOR: c_int = 4;
 
-static scheduler: SyncUnsafeCell<MaybeUninit<Scheduler>> = SyncUnsafeCell::new(MaybeUninit::uninit());
+static scheduler: spin::Mutex<Scheduler> = spin::Mutex::new(Scheduler::new());
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
@@ -48,11 +54,72 @@ fn panic(info: &PanicInfo) -> ! {
     }
 }
 
+// Each module is mounted at the path given by its cmdline in limine.conf, "/" when it is empty.
+// Archives become read-only file systems and the other modules a single file
+fn mount_modules(modules: &[boot_module]){
+    let mut filesystems: Vec<(PathBuf, String, MountPoint)> = Vec::new();
+    for module in modules{
+        let name = unsafe{CStr::from_ptr(module.path)}.to_string_lossy().into_owned();
+        let cmdline = unsafe{CStr::from_ptr(module.cmdline)}.to_string_lossy();
+        let path = PathBuf::from(cmdline.trim());
+
+        // The modules are used in place and never freed
+        let module_data: &'static [u8] = unsafe{slice::from_raw_parts(module.address as *const u8, module.size as usize)};
+        let data = match compression::decompress(module_data){
+            Ok(Some(data)) => {
+                log!(LogLevel::Debug, "decompressed {}: {} bytes", name, data.len());
+                data.leak()
+            },
+            Ok(None) => module_data,
+            Err(error) => {
+                log!(LogLevel::Error, "Failed to decompress {}: {:?}", name, error);
+                continue;
+            }
+        };
+        let filesystem = match fs::load_archive(data){
+            Ok(filesystem) => filesystem,
+            Err(fs::ArchiveError::UnknownFormat) => fs::raw::new_raw_file(data),
+            Err(error) => {
+                log!(LogLevel::Error, "Failed to load {}: {:?}", name, error);
+                continue;
+            }
+        };
+        filesystems.push((path, name, filesystem));
+    }
+
+    // Continue with an empty root file system instead of panicking here
+    if !filesystems.iter().any(|(path, _, filesystem)| path.is_empty() && filesystem.get_root().is_folder()){
+        log!(LogLevel::Warning, "No root file system in the modules, using an empty one");
+        filesystems.push((PathBuf::new(), "tmpfs".to_string(), fs::tmpfs::new_tmpfs()));
+    }
+    // A file system is mounted after the ones containing its path
+    filesystems.sort_by_key(|(path, ..)| path.len());
+    for (path, name, filesystem) in filesystems{
+        log!(LogLevel::Debug, "mounting {} at {}", name, path);
+        if let Err(error) = fs::mount(&path.to_string(), filesystem){
+            log!(LogLevel::Error, "Failed to mount {} at {}: {:?}", name, path, error);
+        }
+    }
+}
+
+/// # Safety
+/// Called once by the C kernel, modules_ptr must point to module_count modules that stay
+/// mapped while the kernel runs and cmdline_ptr to a NUL terminated string
 #[unsafe(no_mangle)]
-pub extern "C" fn rust_kmain(initrd_ptr: *const core::ffi::c_void, initrd_size: usize, rsdp: *mut core::ffi::c_void) -> !{
-    println!("Hello from rust!");
+pub unsafe extern "C" fn rust_kmain(modules_ptr: *const boot_module, module_count: usize, cmdline_ptr: *const c_char, rsdp: *mut core::ffi::c_void) -> !{
+    let ignored_options = cmdline::init(&unsafe{CStr::from_ptr(cmdline_ptr)}.to_string_lossy());
+    if cmdline::get_console() == Console::Serial{
+        serial::init();
+    }
+    log!(LogLevel::Info, "Hello from rust!");
+    for option in ignored_options{
+        log!(LogLevel::Warning, "Ignored kernel option: {:?}", option);
+    }
+
+    percpu::init(0);
+    syscall::init_syscall_instruction();
 
-    println!("Setup apic");
+    log!(LogLevel::Info, "Setup apic");
 
     apic::setup_apic();
     
@@ -64,67 +131,56 @@ pub extern "C" fn rust_kmain(initrd_ptr: *const core::ffi::c_void, initrd_size:
     unsafe{
         apic::setup_io_apic_addr(io_apic_addr);
     }
-    println!("I/O APIC version: {}", unsafe{apic::get_io_apic_version()});
+    log!(LogLevel::Info, "I/O APIC version: {}", unsafe{apic::get_io_apic_version()});
     apic::set_task_priority(0);
-    println!("reading initrd");
-
-    let data = unsafe{Box::from_raw(slice::from_raw_parts_mut(initrd_ptr as *mut u8, initrd_size))};
-
-
-    println!("parsing tar header");
-    let headers = fs::ustar::parse_file(&data);
-
-    let vfs = fs::ustar::headers_to_fs(headers, data);
-
-    let mountpoint = vfs.get_mountpoint().unwrap();
+    log!(LogLevel::Info, "mounting the modules");
+    let modules = unsafe{slice::from_raw_parts(modules_ptr, module_count)};
+    mount_modules(modules);
+    if let Err(error) = fs::mount("/tmp", fs::tmpfs::new_tmpfs()){
+        log!(LogLevel::Error, "Failed to mount /tmp: {:?}", error);
+    }
    
-    println!("reading init.elf");
-    let init_elf_path = vfs::PathBuf::from("init.elf");
-    let init_inode = vfs.find(init_elf_path).unwrap();
-    let init_inode_size = init_inode.get_size(mountpoint).unwrap();
-    let init_elf_data = vfs.read(mountpoint, init_inode, 0, init_inode_size).unwrap();
+    let init_path = cmdline::get_init();
+    log!(LogLevel::Info, "reading {}", init_path);
+    let init_elf_data = match fs::read_file(&init_path){
+        Ok(data) => data,
+        Err(error) => panic!("Failed to read {}: {:?}", init_path, error),
+    };
     
     
-    println!("Setup pit");
+    log!(LogLevel::Info, "Setup pit");
     apic::setup_PIT_interrupt(&madt);
-    println!("Setup keyboard");
+    log!(LogLevel::Info, "Setup keyboard");
     apic::setup_keyboard_interrupt(&madt);
-    println!("Setup apic timer");
+    log!(LogLevel::Info, "Setup apic timer");
     apic::timer::setup_apic_timer();
 
 
-    println!("Starting other cores if available");
-    unsafe { start_slave_core() };
+    // The timer interrupt already uses the scheduler
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut scheduler_ref = scheduler::get_scheduler();
+        let init_process = Process::from_elf(scheduler_ref.allocate_pid(), &init_elf_data).expect("Failed to load the init process");
+        scheduler_ref.add_process(init_process, 0);
+    });
 
-
-    let (entry_point, sp, heap_start, heap_len) = elf::load_elf_file(&init_elf_data);
-    println!("Entry point 0x{:x}", entry_point);
-    unsafe { scheduler.get().as_mut().unwrap().write(Scheduler::new())};
-    let init_process = Process::new(1, entry_point, sp, heap_start, heap_len);
-    
-
-    unsafe{
-        let scheduler_ref = scheduler.get().as_mut().unwrap().assume_init_mut();
-        scheduler_ref.add_to_queue(init_process);
-        scheduler_ref.next_process();
-        scheduler_ref.resume_current_process();
+    // The timer calibration must be done before, the other cores reuse it
+    if cmdline::is_smp_enabled(){
+        log!(LogLevel::Info, "Starting other cores if available");
+        unsafe { start_slave_core() };
     }
 
-    println!("Execution ended");
-
-    loop {
-        hlt();
-    }
+    scheduler::run();
 }
 
 
 #[unsafe(no_mangle)]
-pub extern "C" fn rust_slave_main(_core_id: u32, rsdp: *mut core::ffi::c_void){
+pub extern "C" fn rust_slave_main(core_id: u32, rsdp: *mut core::ffi::c_void) -> !{
+    percpu::init(core_id);
+    syscall::init_syscall_instruction();
     let rsdt = unsafe { rsdt::RSDT::get_RSDT(rsdp) };
     let _madt = MADT::from_rsdt(&rsdt);
     apic::setup_apic();
-    x86_64::instructions::interrupts::enable();
-    loop {
-        hlt();
-    }
+    apic::set_task_priority(0);
+    apic::timer::start_apic_timer();
+    scheduler::run();
 }
\ No newline at end of file
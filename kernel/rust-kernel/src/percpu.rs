@@ -0,0 +1,61 @@
+use alloc::boxed::Box;
+use x86_64::{
+    VirtAddr,
+    registers::model_specific::{GsBase, KernelGsBase},
+};
+
+use crate::{apic, scheduler::process::Process};
+
+// Data of the core running the code. The GS base points to it while in the kernel,
+// the interrupt stubs swap it with the user GS base (swapgs) when entering or leaving user mode.
+// The offsets of the first fields are used by usermode.asm and interrupts.asm.
+#[repr(C)]
+pub struct PerCpu{
+    self_ptr: *mut PerCpu,
+    kernel_stack: u64, // Stack used by interrupts coming from user mode, set by usermode_resume
+    user_stack: u64, // Scratch used by syscall_entry before it switches to the kernel stack
+    cpu_id: u32, // Index of the GDT and TSS of the core, 0 for the BSP
+    lapic_id: u32,
+    pub lapic_base: u64,
+    // Only accessed through the scheduler
+    pub current_process: Option<Process>,
+}
+
+// Must be called once on each core before any interrupt that uses it
+pub fn init(cpu_id: u32){
+    let percpu = Box::leak(Box::new(PerCpu {
+        self_ptr: core::ptr::null_mut(),
+        kernel_stack: 0,
+        user_stack: 0,
+        cpu_id,
+        lapic_id: apic::local_apic_id() as u32,
+        lapic_base: 0,
+        current_process: None,
+    }));
+    percpu.self_ptr = percpu;
+    GsBase::write(VirtAddr::from_ptr(percpu.self_ptr));
+    KernelGsBase::write(VirtAddr::zero());
+}
+
+// The kernel isn't preemptible, so the code never moves to another core while using it
+pub fn get() -> &'static mut PerCpu{
+    unsafe {
+        let ptr: *mut PerCpu;
+        core::arch::asm!("mov {}, gs:[0]", out(reg) ptr, options(nostack, readonly, preserves_flags));
+        &mut *ptr
+    }
+}
+
+impl PerCpu{
+    pub fn get_cpu_id(&self) -> u32{
+        self.cpu_id
+    }
+
+    pub fn get_lapic_id(&self) -> u32{
+        self.lapic_id
+    }
+
+    pub fn get_kernel_stack(&self) -> u64{
+        self.kernel_stack
+    }
+}
@@ -1,11 +1,17 @@
-use crate::kputc;
+use crate::{cmdline::{self, Console}, kputc, serial};
 use core::fmt;
 use spin::Mutex;
 
 pub fn write_string(s: &str){
+    let console = cmdline::get_console();
     for byte in s.bytes(){
-        match byte{
-            0x00..0x80 => unsafe { kputc(byte as i8)}
+        match (byte, console){
+            (0x00..0x80, Console::Screen) => unsafe { kputc(byte as i8)}
+            (b'\n', Console::Serial) => {
+                serial::write_byte(b'\r');
+                serial::write_byte(b'\n');
+            }
+            (0x00..0x80, Console::Serial) => serial::write_byte(byte),
             _ => ()
         }
     }
@@ -44,3 +50,13 @@ macro_rules! println {
     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)))
 }
 
+
+// Print the message if the loglevel= kernel option allows it
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
+        if $level <= $crate::cmdline::get_loglevel() {
+            $crate::println!($($arg)*)
+        }
+    };
+}
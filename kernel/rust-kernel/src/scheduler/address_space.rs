@@ -0,0 +1,94 @@
+use x86_64::{
+    PhysAddr,
+    registers::control::{Cr3, Cr3Flags},
+    structures::paging::PhysFrame,
+};
+
+use crate::{errno::Errno, clone_user_page_directory, create_user_page_directory, free_user_page_directory, get_kernel_page_directory, map_page_in};
+
+// Page directory of a process, the kernel half is shared with every other address space
+#[derive(Debug)]
+pub struct AddressSpace{
+    pml4: usize, // physical address
+}
+
+impl AddressSpace{
+    // Empty user half, None if there is not enough memory for the page directory
+    pub fn new_user() -> Option<Self>{
+        let pml4 = unsafe { create_user_page_directory() };
+        if pml4 == 0{
+            None
+        }else{
+            Some(AddressSpace { pml4 })
+        }
+    }
+
+    /// Copy of every user page of the page directory at pml4, None if there is not enough memory.
+    ///
+    /// # Safety
+    /// pml4 must be the physical address of a user page directory. It is read without the
+    /// scheduler lock, so its process must not run, exec or exit until the copy is done
+    pub unsafe fn try_clone_from(pml4: usize) -> Option<Self>{
+        let pml4 = unsafe { clone_user_page_directory(pml4) };
+        if pml4 == 0{
+            None
+        }else{
+            Some(AddressSpace { pml4 })
+        }
+    }
+
+    pub fn get_pml4(&self) -> usize{
+        self.pml4
+    }
+
+    // Fails if there is not enough memory for the page tables
+    pub fn map(&self, phys_addr: usize, virt_addr: usize, flags: i32) -> Result<(), Errno>{
+        if unsafe { map_page_in(self.pml4, phys_addr, virt_addr, flags) } != 0{
+            return Err(Errno::ENOMEM);
+        }
+        Ok(())
+    }
+
+    pub fn is_active(&self) -> bool{
+        let (frame, _) = Cr3::read_raw();
+        frame.start_address().as_u64() as usize == self.pml4
+    }
+
+    /// Load the page directory in cr3 of the current core.
+    ///
+    /// # Safety
+    /// Must be called with the interrupts disabled or the scheduler locked, and the address space
+    /// must outlive its use on this core: another page directory has to be loaded before it is
+    /// dropped on another core. Drop only switches back to the kernel one on the current core
+    pub unsafe fn activate(&self){
+        if !self.is_active(){
+            unsafe { load_page_directory(self.pml4) };
+        }
+    }
+
+    // Run f with this address space loaded, used to access user memory of a process that isn't running
+    pub fn with_active<R, F: FnOnce() -> R>(&self, f: F) -> R{
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            let (previous_frame, previous_flags) = Cr3::read();
+            unsafe { self.activate() };
+            let result = f();
+            unsafe { Cr3::write(previous_frame, previous_flags) };
+            result
+        })
+    }
+}
+
+impl Drop for AddressSpace{
+    // Free every page used by the process
+    fn drop(&mut self){
+        if self.is_active(){
+            unsafe { load_page_directory(get_kernel_page_directory()) };
+        }
+        unsafe { free_user_page_directory(self.pml4) };
+    }
+}
+
+unsafe fn load_page_directory(pml4: usize){
+    let frame = PhysFrame::containing_address(PhysAddr::new(pml4 as u64));
+    unsafe { Cr3::write(frame, Cr3Flags::empty()) };
+}
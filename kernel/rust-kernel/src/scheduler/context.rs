@@ -0,0 +1,79 @@
+use x86_64::{
+    VirtAddr,
+    registers::{
+        model_specific::{FsBase, GsBase, KernelGsBase},
+        segmentation::{FS, GS, Segment, SegmentSelector},
+    },
+};
+
+use crate::interrupts::InterruptFrame;
+
+const USER_DATA_SEGMENT: u64 = 0x3B;
+const USER_CODE_SEGMENT: u64 = 0x43;
+const DEFAULT_RFLAGS: u64 = 0x202; // Interrupts enabled
+
+// Complete user state of a process.
+// GPRs, RFLAGS, CS/SS and DS/ES are saved by the interrupt stubs in the frame,
+// FS/GS are never modified by the kernel so they are only saved on context switch.
+// While in the kernel the user GS base is in KernelGsBase, GsBase points to the per-CPU data.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Context{
+    pub frame: InterruptFrame,
+    pub fs: u16,
+    pub gs: u16,
+    pub fs_base: u64,
+    pub gs_base: u64,
+}
+
+impl Context{
+    pub fn new_user(ip: usize, sp: usize) -> Self{
+        let frame = InterruptFrame{
+            es: USER_DATA_SEGMENT,
+            ds: USER_DATA_SEGMENT,
+            rip: ip as u64,
+            cs: USER_CODE_SEGMENT,
+            rflags: DEFAULT_RFLAGS,
+            rsp: sp as u64,
+            ss: USER_DATA_SEGMENT,
+            ..Default::default()
+        };
+        Context {
+            frame,
+            fs: USER_DATA_SEGMENT as u16,
+            gs: USER_DATA_SEGMENT as u16,
+            fs_base: 0,
+            gs_base: 0,
+        }
+    }
+
+    pub fn save(&mut self, frame: &InterruptFrame){
+        self.frame = *frame;
+        self.fs = FS::get_reg().0;
+        self.gs = GS::get_reg().0;
+        self.fs_base = FsBase::read().as_u64();
+        self.gs_base = KernelGsBase::read().as_u64();
+    }
+
+    // Load FS/GS and write the saved frame over the current one,
+    // the interrupt stub will restore the remaining registers
+    pub fn restore(&self, frame: &mut InterruptFrame){
+        let interrupt_code = frame.interrupt_code;
+        let error_code = frame.error_code;
+        *frame = self.frame;
+        frame.interrupt_code = interrupt_code;
+        frame.error_code = error_code;
+        self.load_fs_gs();
+    }
+
+    pub fn load_fs_gs(&self){
+        unsafe {
+            // Loading a selector overwrites the base, so the bases are written last
+            let percpu = GsBase::read();
+            FS::set_reg(SegmentSelector(self.fs));
+            GS::set_reg(SegmentSelector(self.gs));
+            FsBase::write(VirtAddr::new_truncate(self.fs_base));
+            GsBase::write(percpu);
+            KernelGsBase::write(VirtAddr::new_truncate(self.gs_base));
+        }
+    }
+}
@@ -1,39 +1,201 @@
-use alloc::collections::vec_deque::VecDeque;
+use alloc::{collections::{btree_map::BTreeMap, vec_deque::VecDeque}, vec::Vec};
+use spin::MutexGuard;
 
-use crate::scheduler::process::Process;
+use crate::{interrupts::InterruptFrame, percpu, scheduler::{process::Process, wait_queue::WaitQueue}, usermode_resume};
 
 pub mod process;
+pub mod context;
+pub mod address_space;
+pub mod wait_queue;
 
+// Woken up every time a process exits
+pub static CHILD_EXIT_QUEUE: WaitQueue = WaitQueue::new();
+
+// Shared by every core, the lock must be taken with interrupts disabled.
+// The process running on a core is kept in its per-CPU data.
 pub struct Scheduler{
-    current_process: Option<Process>,
+    parents: BTreeMap<usize, usize>, // pid -> parent pid (0 if none) of every process not ended
     queue: VecDeque<Process>,
+    blocked: BTreeMap<usize, Process>,
+    sleeping: Vec<(u64, usize)>, // (tick to wake up at, pid)
+    exited: BTreeMap<usize, ExitStatus>,
+    next_pid: usize,
+    ticks: u64,
+}
+
+// Kept until the parent collects it with waitpid
+#[derive(Debug, Clone, Copy)]
+pub struct ExitStatus{
+    parent_pid: usize,
+    exit_code: u64,
+}
+
+pub enum WaitResult{
+    Exited(u64),
+    Running,
+    NoChild,
+}
+
+pub fn get_scheduler() -> MutexGuard<'static, Scheduler>{
+    crate::scheduler.lock()
 }
 
 impl Scheduler{
-    pub fn new() -> Self{
-        Scheduler { current_process: None, queue: VecDeque::new() }
+    pub const fn new() -> Self{
+        Scheduler {
+            parents: BTreeMap::new(),
+            queue: VecDeque::new(),
+            blocked: BTreeMap::new(),
+            sleeping: Vec::new(),
+            exited: BTreeMap::new(),
+            next_pid: 1,
+            ticks: 0,
+        }
+    }
+
+    pub fn allocate_pid(&mut self) -> usize{
+        let pid = self.next_pid;
+        self.next_pid += 1;
+        pid
     }
 
-    pub fn add_to_queue(&mut self, process: Process){
+    pub fn add_process(&mut self, process: Process, parent_pid: usize){
+        self.parents.insert(process.get_pid(), parent_pid);
         self.queue.push_back(process);
     }
 
-    pub unsafe fn end_current_process(&mut self){
-        self.current_process = None
+    // Drop the current process, which frees its memory, and keep its exit status for the parent
+    pub unsafe fn end_current_process(&mut self, exit_code: u64){
+        if let Some(process) = percpu::get().current_process.take(){
+            let pid = process.get_pid();
+            let parent_pid = self.parents.remove(&pid).unwrap_or(0);
+            // Nobody can collect the status of the children anymore
+            self.exited.retain(|_, status| status.parent_pid != pid);
+            for child_parent_pid in self.parents.values_mut().filter(|parent| **parent == pid){
+                *child_parent_pid = 0;
+            }
+            if parent_pid != 0{
+                self.exited.insert(pid, ExitStatus { parent_pid, exit_code });
+            }
+        }
+    }
+
+    // Save the current process and keep it out of the queue until unblock is called with its pid
+    pub fn block_current_process(&mut self, frame: &InterruptFrame) -> Option<usize>{
+        let mut process = percpu::get().current_process.take()?;
+        process.save_context(frame);
+        let pid = process.get_pid();
+        self.blocked.insert(pid, process);
+        Some(pid)
+    }
+
+    pub fn unblock(&mut self, pid: usize){
+        if let Some(process) = self.blocked.remove(&pid){
+            self.queue.push_back(process);
+        }
     }
 
-    pub unsafe fn next_process(&mut self){
-        let new_process = self.queue.pop_front();
-        self.current_process = new_process;
+    pub fn sleep_current_process(&mut self, ticks: u64, frame: &InterruptFrame){
+        if let Some(pid) = self.block_current_process(frame){
+            self.sleeping.push((self.ticks + ticks, pid));
+        }
     }
 
-    pub fn resume_current_process(&self){
-        if let Some(process) = &self.current_process{
-            unsafe { process.execute_process(); };
+    // Called on every timer interrupt
+    pub fn tick(&mut self){
+        self.ticks += 1;
+        let ticks = self.ticks;
+        let mut awake = Vec::new();
+        self.sleeping.retain(|(wake_up_tick, pid)| {
+            if *wake_up_tick <= ticks{
+                awake.push(*pid);
+                false
+            }else{
+                true
+            }
+        });
+        for pid in awake{
+            self.unblock(pid);
+        }
+    }
+
+    // Round robin: save the interrupted process at the back of the queue and
+    // modify the interrupt frame to return into the next one.
+    pub fn schedule(&mut self, frame: &mut InterruptFrame){
+        if self.queue.is_empty(){
+            return;
+        }
+        if let Some(mut process) = percpu::get().current_process.take(){
+            process.save_context(frame);
+            self.queue.push_back(process);
+        }
+        self.load_next_process(frame);
+    }
+
+    // Modify the frame to return into the next process of the queue, the process
+    // running on this core must have been saved or ended before.
+    // Returns false if there is no process to run.
+    pub fn load_next_process(&mut self, frame: &mut InterruptFrame) -> bool{
+        let Some(process) = self.queue.pop_front() else{
+            return false;
+        };
+        process.restore_context(frame);
+        unsafe { process.get_address_space().activate() };
+        percpu::get().current_process = Some(process);
+        true
+    }
+
+    // Collect the exit code of a child of the current process
+    pub fn try_wait(&mut self, pid: usize) -> WaitResult{
+        let Some(current_pid) = self.get_current_process().map(|process| process.get_pid()) else{
+            return WaitResult::NoChild;
+        };
+        if let Some(status) = self.exited.get(&pid) && status.parent_pid == current_pid{
+            let exit_code = status.exit_code;
+            self.exited.remove(&pid);
+            return WaitResult::Exited(exit_code);
+        }
+        if self.parents.get(&pid) == Some(&current_pid){
+            WaitResult::Running
+        }else{
+            WaitResult::NoChild
         }
     }
 
     pub fn get_current_process(&self) -> Option<&Process>{
-        self.current_process.as_ref()
+        percpu::get().current_process.as_ref()
     }
-}
\ No newline at end of file
+
+    pub fn get_current_process_mut(&mut self) -> Option<&mut Process>{
+        percpu::get().current_process.as_mut()
+    }
+}
+
+// Switch to the next process once the current one has been saved or ended,
+// must be called with interrupts disabled and without holding the scheduler lock.
+pub fn switch_to_next_process(frame: &mut InterruptFrame){
+    let loaded = get_scheduler().load_next_process(frame);
+    if !loaded{
+        idle(frame);
+    }
+}
+
+// Wait until a process can be loaded in the frame, the lock is released while halted
+// so that the other cores can add processes to the queue.
+fn idle(frame: &mut InterruptFrame){
+    loop {
+        x86_64::instructions::interrupts::disable();
+        if get_scheduler().load_next_process(frame){
+            return;
+        }
+        x86_64::instructions::interrupts::enable_and_hlt();
+    }
+}
+
+// Entry point of every core once the kernel is initialized
+pub fn run() -> !{
+    let mut frame = InterruptFrame::default();
+    idle(&mut frame);
+    unsafe { usermode_resume(&frame as *const InterruptFrame as *const core::ffi::c_void) };
+    unreachable!();
+}
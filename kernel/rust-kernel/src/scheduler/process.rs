@@ -2,13 +2,15 @@ use core::{alloc::Layout, ptr::NonNull};
 
 use alloc::collections::btree_map::BTreeMap;
 
-use crate::usermode_switch;
+use crate::{elf, errno::Errno, fs::{file::FileTable, vfs::PathBuf}, interrupts::InterruptFrame, scheduler::{address_space::AddressSpace, context::Context}};
 
 pub struct Process{
     pid: usize,
-    saved_stack_pointer: usize,
-    saved_instruction_pointer: usize,
-    allocations: spin::Mutex<ProcessAllocationData>
+    context: Context,
+    address_space: AddressSpace,
+    allocations: spin::Mutex<ProcessAllocationData>,
+    files: FileTable,
+    cwd: PathBuf,
 }
 
 struct ProcessAllocationData{
@@ -24,19 +26,80 @@ impl ProcessAllocationData{
         let allocations = BTreeMap::new();
         ProcessAllocationData { allocator, allocations }
     }
+
+    // The allocator metadata is stored in the heap, it is at the same addresses in a copied
+    // address space so a bitwise copy of the allocator stays valid for the copy
+    fn duplicate(&self) -> Self{
+        let allocator = unsafe { core::ptr::read(&self.allocator) };
+        ProcessAllocationData { allocator, allocations: self.allocations.clone() }
+    }
 }
 
 impl Process{
-    pub fn new(pid: usize, ip: usize, sp: usize, heap_start: usize, heap_len: usize) -> Self{
-        let allocations = spin::Mutex::new(ProcessAllocationData::new(heap_start, heap_len));
-        Process { pid, saved_stack_pointer: sp, saved_instruction_pointer: ip, allocations }
+    pub fn new(pid: usize, ip: usize, sp: usize, heap_start: usize, heap_len: usize, address_space: AddressSpace) -> Self{
+        // The allocator stores its metadata in the process heap
+        let allocation_data = address_space.with_active(|| ProcessAllocationData::new(heap_start, heap_len));
+        let allocations = spin::Mutex::new(allocation_data);
+        let context = Context::new_user(ip, sp);
+        Process { pid, context, address_space, allocations, files: FileTable::new(), cwd: PathBuf::new() }
+    }
+
+    pub fn from_elf(pid: usize, data: &[u8]) -> Result<Self, Errno>{
+        let address_space = AddressSpace::new_user().ok_or(Errno::ENOMEM)?;
+        let (ip, sp, heap_start, heap_len) = elf::load_elf_file(data, &address_space)?;
+        Ok(Process::new(pid, ip, sp, heap_start, heap_len, address_space))
     }
 
+    // Copy of the process that resumes from the frame with 0 in rax,
+    // address_space is a copy of the one of this process
+    pub fn fork(&self, pid: usize, frame: &InterruptFrame, address_space: AddressSpace) -> Self{
+        let mut context = self.context;
+        context.save(frame);
+        context.frame.rax = 0;
+        let allocations = spin::Mutex::new(self.allocations.lock().duplicate());
+        Process { pid, context, address_space, allocations, files: self.files.clone(), cwd: self.cwd.clone() }
+    }
 
-    pub unsafe fn execute_process(&self){
-        unsafe{
-            usermode_switch(self.saved_instruction_pointer, self.saved_stack_pointer);
-        }
+    // Replace the program run by the process with the one loaded in process, the pid, the open
+    // files and the working folder are kept. Returns the previous program so it can be freed later
+    pub fn exec(&mut self, mut process: Process) -> Process{
+        process.pid = self.pid;
+        process.files = core::mem::take(&mut self.files);
+        process.cwd = core::mem::replace(&mut self.cwd, PathBuf::new());
+        core::mem::replace(self, process)
+    }
+
+    pub fn get_pid(&self) -> usize{
+        self.pid
+    }
+
+    pub fn save_context(&mut self, frame: &InterruptFrame){
+        self.context.save(frame);
+    }
+
+    pub fn restore_context(&self, frame: &mut InterruptFrame){
+        self.context.restore(frame);
+    }
+
+    pub fn get_address_space(&self) -> &AddressSpace{
+        &self.address_space
+    }
+
+    pub fn get_files(&self) -> &FileTable{
+        &self.files
+    }
+
+    pub fn get_files_mut(&mut self) -> &mut FileTable{
+        &mut self.files
+    }
+
+    // Folder used to resolve the relative paths given to the syscalls
+    pub fn get_cwd(&self) -> &PathBuf{
+        &self.cwd
+    }
+
+    pub fn set_cwd(&mut self, cwd: PathBuf){
+        self.cwd = cwd;
     }
 
     pub fn malloc(&self, layout: Layout) -> usize{
@@ -50,14 +113,15 @@ impl Process{
         }
     }
 
-    pub fn free(&self, ptr: u64){
+    // Only pointers returned by malloc can be freed
+    pub fn free(&self, ptr: u64) -> Result<(), Errno>{
         let mut allocations = self.allocations.lock();
         let addr = ptr as usize;
-        let layout = allocations.allocations.remove(&addr).unwrap();
-        let ptr = NonNull::new(ptr as *mut u8).unwrap();
+        let layout = allocations.allocations.remove(&addr).ok_or(Errno::EINVAL)?;
+        let ptr = NonNull::new(ptr as *mut u8).ok_or(Errno::EINVAL)?;
         unsafe {
             allocations.allocator.free(ptr, layout);
         }
-
+        Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,43 @@
+use alloc::collections::vec_deque::VecDeque;
+use spin::{Mutex, MutexGuard};
+
+use crate::{interrupts::InterruptFrame, scheduler::{get_scheduler, switch_to_next_process, Scheduler}};
+
+// Processes blocked until an event happens, they are woken up by an interrupt handler or another process
+pub struct WaitQueue{
+    pids: Mutex<VecDeque<usize>>,
+}
+
+impl WaitQueue{
+    pub const fn new() -> Self{
+        WaitQueue { pids: Mutex::new(VecDeque::new()) }
+    }
+
+    // Block the current process and switch to the next one, must be called with interrupts disabled.
+    // The caller checks the wake up condition while holding the scheduler lock so that a wake up
+    // from another core can't be missed.
+    // The process resumes from the frame once woken up, a syscall can rewind rip to be restarted.
+    pub fn sleep(&self, mut scheduler_ref: MutexGuard<Scheduler>, frame: &mut InterruptFrame){
+        if let Some(pid) = scheduler_ref.block_current_process(frame){
+            self.pids.lock().push_back(pid);
+        }
+        drop(scheduler_ref);
+        switch_to_next_process(frame);
+    }
+
+    pub fn wake_one(&self){
+        let mut scheduler_ref = get_scheduler();
+        let pid = self.pids.lock().pop_front();
+        if let Some(pid) = pid{
+            scheduler_ref.unblock(pid);
+        }
+    }
+
+    pub fn wake_all(&self){
+        let mut scheduler_ref = get_scheduler();
+        let pids = core::mem::take(&mut *self.pids.lock());
+        for pid in pids{
+            scheduler_ref.unblock(pid);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{inb, outb};
+
+const COM1: u16 = 0x3F8;
+
+const DATA: u16 = COM1;
+const INTERRUPT_ENABLE: u16 = COM1 + 1;
+const FIFO_CONTROL: u16 = COM1 + 2;
+const LINE_CONTROL: u16 = COM1 + 3;
+const MODEM_CONTROL: u16 = COM1 + 4;
+const LINE_STATUS: u16 = COM1 + 5;
+
+const LINE_STATUS_TRANSMIT_EMPTY: u8 = 0x20;
+
+// 38400 bauds, 8 bits, no parity, one stop bit, without interrupts
+pub fn init(){
+    unsafe{
+        outb(INTERRUPT_ENABLE, 0);
+        outb(LINE_CONTROL, 0x80); // The next two registers are the baud rate divisor
+        outb(DATA, 3);
+        outb(INTERRUPT_ENABLE, 0);
+        outb(LINE_CONTROL, 0x03);
+        outb(FIFO_CONTROL, 0xC7);
+        outb(MODEM_CONTROL, 0x03);
+    }
+}
+
+pub fn write_byte(byte: u8){
+    unsafe{
+        while inb(LINE_STATUS) & LINE_STATUS_TRANSMIT_EMPTY == 0 {}
+        outb(DATA, byte);
+    }
+}
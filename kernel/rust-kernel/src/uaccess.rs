@@ -0,0 +1,85 @@
+use alloc::vec::Vec;
+
+use crate::{errno::Errno, find_page_entry, PTE_PRESENT, PTE_READ_WRITE, PTE_USER_SUPERVISOR};
+
+// Access to the memory of the current process from a syscall.
+// The pages are checked in the current page directory before the kernel touches them,
+// so a bad pointer gives EFAULT instead of a page fault or a read of kernel memory.
+
+const PAGE_SIZE: u64 = 4096;
+pub const USER_SPACE_END: u64 = 0x0000_8000_0000_0000; // The upper half is shared by the kernel
+
+fn check_user_page(addr: u64, write: bool) -> Result<(), Errno>{
+    let mut flags = (PTE_PRESENT | PTE_USER_SUPERVISOR) as usize;
+    if write{
+        flags |= PTE_READ_WRITE as usize;
+    }
+    let entry = unsafe { find_page_entry(addr as usize) };
+    if entry & flags == flags{
+        Ok(())
+    }else{
+        Err(Errno::EFAULT)
+    }
+}
+
+// Check that [addr, addr + len) is mapped and accessible from user mode
+pub fn check_user_range(addr: u64, len: usize, write: bool) -> Result<(), Errno>{
+    if len == 0{
+        return Ok(());
+    }
+    let end = addr.checked_add(len as u64).ok_or(Errno::EFAULT)?;
+    if end > USER_SPACE_END{
+        return Err(Errno::EFAULT);
+    }
+    let mut page = addr & !(PAGE_SIZE - 1);
+    while page < end{
+        check_user_page(page, write)?;
+        page += PAGE_SIZE;
+    }
+    Ok(())
+}
+
+pub fn copy_from_user(dst: &mut [u8], src: u64) -> Result<(), Errno>{
+    check_user_range(src, dst.len(), false)?;
+    let src = unsafe { core::slice::from_raw_parts(src as *const u8, dst.len()) };
+    dst.copy_from_slice(src);
+    Ok(())
+}
+
+// Copy into a new buffer, the range is checked before allocating it
+pub fn copy_buffer_from_user(src: u64, len: usize) -> Result<Vec<u8>, Errno>{
+    check_user_range(src, len, false)?;
+    let src = unsafe { core::slice::from_raw_parts(src as *const u8, len) };
+    Ok(Vec::from(src))
+}
+
+pub fn copy_to_user(dst: u64, src: &[u8]) -> Result<(), Errno>{
+    check_user_range(dst, src.len(), true)?;
+    let dst = unsafe { core::slice::from_raw_parts_mut(dst as *mut u8, src.len()) };
+    dst.copy_from_slice(src);
+    Ok(())
+}
+
+// Copy a null terminated string without its terminator,
+// fails with ENAMETOOLONG if there is no terminator in the first max_len bytes
+pub fn strncpy_from_user(src: u64, max_len: usize) -> Result<Vec<u8>, Errno>{
+    let mut string = Vec::new();
+    let mut addr = src;
+    while string.len() < max_len{
+        if addr >= USER_SPACE_END{
+            return Err(Errno::EFAULT);
+        }
+        // Check one page at a time, the string can end before the next one
+        let page_end = (addr & !(PAGE_SIZE - 1)) + PAGE_SIZE;
+        let len = ((page_end - addr) as usize).min(max_len - string.len());
+        check_user_range(addr, len, false)?;
+        let chunk = unsafe { core::slice::from_raw_parts(addr as *const u8, len) };
+        if let Some(end) = chunk.iter().position(|c| *c == 0){
+            string.extend_from_slice(&chunk[..end]);
+            return Ok(string);
+        }
+        string.extend_from_slice(chunk);
+        addr += len as u64;
+    }
+    Err(Errno::ENAMETOOLONG)
+}
@@ -68,8 +68,9 @@ void gdt_init(uint32_t core_id) {
     gdt_set_entry(4 + offset, 0, 0xffffffff, 0, 1, 0, 0, 1, 1, 1, 0); // 32 bit data segment
     gdt_set_entry(5 + offset, 0, 0xffffffff, 0, 1, 1, 0, 1, 1, 0, 1); // 64 bit kernel code segment
     gdt_set_entry(6 + offset, 0, 0xffffffff, 0, 1, 0, 0, 1, 1, 0, 1); // 64 bit kernel data segment
-    gdt_set_entry(7 + offset, 0, 0xffffffff, 3, 1, 1, 0, 1, 1, 0, 1); // 64 bit user code segment
-    gdt_set_entry(8 + offset, 0, 0xffffffff, 3, 1, 0, 0, 1, 1, 0, 1); // 64 bit user data segment
+    // sysret expects the user data segment right before the user code segment
+    gdt_set_entry(7 + offset, 0, 0xffffffff, 3, 1, 0, 0, 1, 1, 0, 1); // 64 bit user data segment
+    gdt_set_entry(8 + offset, 0, 0xffffffff, 3, 1, 1, 0, 1, 1, 0, 1); // 64 bit user code segment
     
     memset(&tss[core_id], 0, sizeof(TSS_t));
 
@@ -1,51 +1,21 @@
 global common_interrupt_handler
+global interrupt_return
+global syscall_entry
 global _isr_addr
 
 %macro no_error_code_interrupt_handler 1
 global interrupt_handler_%1
 interrupt_handler_%1:
-    push qword 0
-    push rsi ; save old rsi
-    xor rsi, rsi
-    mov esi, [rsp+8]
+    push qword 0 ; dummy error code
     push qword %1
-
-    pusha64
-
-    call interrupt_handler
-
-    popa64
-
-    add rsp, 8
-
-    pop rsi ; restore old rsi
-
-    add rsp, 8
-
-    iretq
+    jmp common_interrupt_handler
 %endmacro
 
 %macro error_code_interrupt_handler 1
 global interrupt_handler_%1
 interrupt_handler_%1:
-    push rsi ; save old rsi
-    xor rsi, rsi
-    mov esi, [rsp+8]
     push qword %1
-
-    pusha64
-
-    call interrupt_handler
-
-    popa64
-
-    add rsp, 8
-
-    pop rsi ; restore old rsi
-
-    add rsp, 8
-
-    iretq
+    jmp common_interrupt_handler
 %endmacro
 
 %macro ISR_ADDR 1
@@ -54,6 +24,21 @@ dq interrupt_handler_%1
 
 extern interrupt_handler
 
+; Offsets in PerCpu (see rust-kernel/src/percpu.rs)
+PERCPU_KERNEL_STACK equ 8
+PERCPU_USER_STACK equ 16
+
+USER_DATA_SEGMENT equ 0x3B
+USER_CODE_SEGMENT equ 0x43
+SYSCALL_VECTOR equ 64
+
+; Offsets in the frame (see interrupt_stack in interrupts.h)
+FRAME_R11 equ 48
+FRAME_RCX equ 112
+FRAME_RIP equ 152
+FRAME_CS equ 160
+FRAME_RFLAGS equ 168
+
 %macro pusha64 0
     push rax
     push rbx
@@ -91,50 +76,90 @@ extern interrupt_handler
 %endmacro
 
 
-%macro push_reg_syscall 0
-    push rbx
-    push rcx
-    push rbp
-    push rdi
-    push rsi
-    push r8
-    push r9
-    push r10
-    push r11
-    push r12
-    push r13
-    push r14
-    push r15
+%macro push_segments 0
+    mov rax, ds
+    push rax
+    mov rax, es
+    push rax
 %endmacro
 
-%macro pop_reg_syscall 0
-    pop r15
-    pop r14
-    pop r13
-    pop r12
-    pop r11
-    pop r10
-    pop r9
-    pop r8
-    pop rsi
-    pop rdi
-    pop rbp
-    pop rcx
-    pop rbx
+%macro pop_segments 0
+    pop rax
+    mov es, ax
+    pop rax
+    mov ds, ax
 %endmacro
 
-extern syscall_handler
+; The handler gets a pointer to the saved frame (see interrupt_stack in interrupts.h)
+; and can modify it to return into another context.
+common_interrupt_handler:
+    ; Coming from user mode, the GS base holds the user value and the per-CPU data is in KernelGsBase
+    test qword [rsp + 24], 3 ; code segment of the interrupted code
+    jz .from_kernel
+    swapgs
+.from_kernel:
+    pusha64
+    push_segments
 
-interrupt_handler_64:
-    push r9
-    mov r9, [rsp + 32] ; get process stack pointer
-    push_reg_syscall
-    call syscall_handler
-    pop_reg_syscall
-    pop r9
+    mov rdi, rsp
+    call interrupt_handler
+
+; Also used by usermode_resume to enter a process from a saved frame
+interrupt_return:
+    pop_segments
+    popa64
+
+    add rsp, 16 ; remove interrupt code and error code
+
+    ; The frame may have been replaced, check the code segment we return to
+    test qword [rsp + 8], 3
+    jz .to_kernel
+    swapgs
+.to_kernel:
     iretq
 
 
+; Entry of the syscall instruction (see LSTAR), rcx holds the user rip and r11 the user rflags.
+; Interrupts are disabled by SFMASK until the kernel stack is loaded.
+; The frame is built like the one of int 0x40 so that the same handler is used.
+syscall_entry:
+    swapgs
+    mov [gs:PERCPU_USER_STACK], rsp
+    mov rsp, [gs:PERCPU_KERNEL_STACK]
+    and rsp, -16 ; the CPU aligns the stack of interrupts the same way
+
+    push qword USER_DATA_SEGMENT
+    push qword [gs:PERCPU_USER_STACK]
+    push r11
+    push qword USER_CODE_SEGMENT
+    push rcx
+    push qword 0 ; dummy error code
+    push qword SYSCALL_VECTOR
+    pusha64
+    push_segments
+
+    mov rdi, rsp
+    call interrupt_handler
+
+    ; sysret takes rip from rcx and rflags from r11, it can only be used if the frame still matches.
+    ; A context switch or a restarted syscall returns through iretq.
+    mov rax, [rsp + FRAME_RIP]
+    cmp rax, [rsp + FRAME_RCX]
+    jne interrupt_return
+    mov rax, [rsp + FRAME_RFLAGS]
+    cmp rax, [rsp + FRAME_R11]
+    jne interrupt_return
+    cmp qword [rsp + FRAME_CS], USER_CODE_SEGMENT
+    jne interrupt_return
+
+    pop_segments
+    popa64
+    add rsp, 16 ; remove interrupt code and error code
+    mov rsp, [rsp + 24] ; user stack pointer saved in the frame
+    swapgs
+    o64 sysret
+
+
 no_error_code_interrupt_handler 0
 no_error_code_interrupt_handler 1
 no_error_code_interrupt_handler 2
@@ -199,6 +224,7 @@ no_error_code_interrupt_handler 60
 no_error_code_interrupt_handler 61
 no_error_code_interrupt_handler 62
 no_error_code_interrupt_handler 63
+no_error_code_interrupt_handler 64 ; syscall
 
 
 
@@ -7,10 +7,10 @@
 
 extern void common_interrupt_handler(void);
 
-extern void rust_interrupt_handler(uint64_t interrupt_code, uint64_t error_code);
+extern void rust_interrupt_handler(interrupt_stack *stack);
 
-void interrupt_handler(interrupt_stack stack){
-    rust_interrupt_handler(stack.interrupt_code, stack.error_code);
+void interrupt_handler(interrupt_stack *stack){
+    rust_interrupt_handler(stack);
 }
 
 typedef struct {
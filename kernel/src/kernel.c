@@ -57,6 +57,12 @@ static volatile struct limine_paging_mode_request paging_mode_request = {
     .min_mode = LIMINE_PAGING_MODE_X86_64_4LVL
 };
 
+__attribute__((used, section(".limine_requests")))
+static volatile struct limine_executable_cmdline_request cmdline_request = {
+    .id = LIMINE_EXECUTABLE_CMDLINE_REQUEST,
+    .revision = 0
+};
+
 __attribute__((used, section(".limine_requests")))
 static volatile struct limine_module_request module_request = {
     .id = LIMINE_MODULE_REQUEST,
@@ -246,7 +252,10 @@ extern uint8_t kernel_wr_end;
 extern uint8_t heap_start;
 extern uint8_t heap_end;
 
-extern void rust_kmain(void *, uintptr_t, void *);
+#define MAX_BOOT_MODULES 16
+static struct boot_module boot_modules[MAX_BOOT_MODULES];
+
+extern void rust_kmain(struct boot_module *, size_t, const char *, void *);
 extern void rust_slave_main(uint32_t, void *);
 extern void init_alloc(uintptr_t, uintptr_t);
 
@@ -292,8 +301,20 @@ void kmain(void){
         hcf();
     }
 
-    struct limine_file *initrd = module_response->modules[0];
-    kprintf("%x %d\n", initrd->address, initrd->size);
+    if(module_response->module_count > MAX_BOOT_MODULES){
+        kprintf("Only the first %d modules are loaded\n", MAX_BOOT_MODULES);
+    }
+    size_t boot_module_count = 0;
+    for(; boot_module_count < module_response->module_count && boot_module_count < MAX_BOOT_MODULES; boot_module_count++){
+        struct limine_file *module = module_response->modules[boot_module_count];
+        kprintf("%s %x %d\n", module->path, module->address, module->size);
+        boot_modules[boot_module_count] = (struct boot_module){
+            .address = module->address,
+            .size = module->size,
+            .path = module->path,
+            .cmdline = module->string,
+        };
+    }
 
     struct limine_hhdm_response *hhdm = hhdm_request.response;
 
@@ -302,12 +323,9 @@ void kmain(void){
 
     memmap = memmap_request.response;
 
-    uintptr_t initrd_start = (uintptr_t)initrd->address;
-    uintptr_t initrd_end = initrd_start + initrd->size;
-
     pmm_init();
     kputs("Initialized physical memory manager\n");
-    vmm_init((uintptr_t)&kernel_ro_start, (uintptr_t)&kernel_ro_end, (uintptr_t)&kernel_wr_start, (uintptr_t)&kernel_wr_end, initrd_start, initrd_end);
+    vmm_init((uintptr_t)&kernel_ro_start, (uintptr_t)&kernel_ro_end, (uintptr_t)&kernel_wr_start, (uintptr_t)&kernel_wr_end, boot_modules, boot_module_count);
     kputs("Initialized virtual memory manager\n");
     idt_init();
     kputs("Initialized interrupt descriptor table\n");
@@ -329,7 +347,14 @@ void kmain(void){
 
     
 
-    rust_kmain(initrd->address, initrd->size, (void *)rsdp_request.response->address);
+    // The cmdline given in limine.conf, the kernel uses the default options without it
+    const char *cmdline = "";
+    if(cmdline_request.response != NULL && cmdline_request.response->cmdline != NULL){
+        cmdline = cmdline_request.response->cmdline;
+    }
+    kprintf("Command line: %s\n", cmdline);
+
+    rust_kmain(boot_modules, boot_module_count, cmdline, (void *)rsdp_request.response->address);
 
     // We're done, just hang...
     hcf();
@@ -337,7 +362,7 @@ void kmain(void){
 
 void slave_core_kmain(struct limine_mp_info * mp_info){
     __asm__ volatile("cli");
-    uint32_t core_id = mp_info->lapic_id;
+    uint32_t core_id = mp_info->extra_argument;
     gdt_init(core_id);
     slave_core_init_vmm();
     slave_load_idt();
@@ -349,15 +374,11 @@ void start_slave_core(void){
     struct limine_mp_response * response = mp_request.response;
     uint64_t cpu_count = response->cpu_count;
     limine_goto_address start_addr = slave_core_kmain;
+    uint64_t next_core_id = 1; // The BSP is core 0
     for(uint64_t i = 0; i < cpu_count && i < CPU_MAX_COUNT; i++){
         if(response->cpus[i]->lapic_id != response->bsp_lapic_id){
+            response->cpus[i]->extra_argument = next_core_id++;
             response->cpus[i]->goto_address = start_addr;
         }
     }
 }
-
-extern void usermode_switch(uintptr_t addr, uintptr_t sp);
-
-void jump_to_usermode(uintptr_t addr, uintptr_t sp){
-    usermode_switch(addr, sp);
-}
@@ -5,10 +5,17 @@
 #include <stddef.h>
 #include <stdint.h>
 
+// Returns an empty page table or NULL if there is not enough memory
 PAGE_DIR create_page_directory(){
     PAGE_DIR page_directory = alloc_page(1);
+    if(page_directory == NULL){
+        return NULL;
+    }
     uint64_t phys_addr = limine_virtual_addr_to_phys_addr(page_directory);
-    map_page_current(phys_addr, page_directory, PTE_PRESENT | PTE_READ_WRITE);
+    if(map_page_current(phys_addr, page_directory, PTE_PRESENT | PTE_READ_WRITE) != 0){
+        free_pages(page_directory, 1);
+        return NULL;
+    }
 
     memset((void *) page_directory, 0, PAGE_SIZE);
 
@@ -21,16 +28,18 @@ static void hcf(void) {
     }
 }
 
+// Returns the physical address of the table at index, it is created if missing.
+// Returns 0 if there is not enough memory to create it
 PAGE_DIR get_pml_entry(PAGE_DIR pml, uintptr_t index, int flags){
     if(pml[index] & 1){
         //entry exist
         // & ~(511) => remove bits 0 to 8;
         return (PAGE_DIR)(pml[index] & ~(511));
     }else{
-        uint64_t page = (uint64_t)alloc_page(1);
-        uint64_t phys_addr = limine_virtual_addr_to_phys_addr(page);
-        map_page_current(phys_addr, page, PTE_PRESENT | PTE_READ_WRITE);
-        memset((void *) page, 0, PAGE_SIZE);
+        PAGE_DIR page = create_page_directory();
+        if(page == NULL){
+            return 0;
+        }
         pml[index] = limine_virtual_addr_to_phys_addr((uint64_t)page) | flags;
         return (PAGE_DIR)(pml[index] & ~(511));
     }
@@ -47,12 +56,14 @@ uintptr_t get_cr3(){
     return res;
 }
 
-void map_page_kernel(uintptr_t phys_addr, uintptr_t virt_addr, int flags){
+int map_page_kernel(uintptr_t phys_addr, uintptr_t virt_addr, int flags){
     PAGE_DIR current_page_directory = (PAGE_DIR) phys_addr_to_limine_virtual_addr(get_cr3());
-    map_page(current_page_directory, phys_addr, virt_addr, flags);
+    return map_page(current_page_directory, phys_addr, virt_addr, flags);
 }
 
-void map_page(PAGE_DIR current_page_directory, uintptr_t phys_addr, uintptr_t virt_addr, int flags){
+// Returns 0 on success or -1 if a page table couldn't be allocated, the tables
+// created before the failure stay in the page directory
+int map_page(PAGE_DIR current_page_directory, uintptr_t phys_addr, uintptr_t virt_addr, int flags){
     uintptr_t index4 = (virt_addr & ((uintptr_t)0x1ff << 39)) >> 39;
     uintptr_t index3 = (virt_addr & ((uintptr_t)0x1ff << 30)) >> 30;
     uintptr_t index2 = (virt_addr & ((uintptr_t)0x1ff << 21)) >> 21;
@@ -60,9 +71,20 @@ void map_page(PAGE_DIR current_page_directory, uintptr_t phys_addr, uintptr_t vi
     
 
     PAGE_DIR pml4 = current_page_directory;
-    PAGE_DIR pml3 = (PAGE_DIR)phys_addr_to_limine_virtual_addr((uintptr_t)get_pml_entry(pml4, index4, flags));
-    PAGE_DIR pml2 = (PAGE_DIR)phys_addr_to_limine_virtual_addr((uintptr_t)get_pml_entry(pml3, index3, flags));
-    uintptr_t temp = (uintptr_t)get_pml_entry(pml2, index2, flags);
+    uintptr_t temp = (uintptr_t)get_pml_entry(pml4, index4, flags);
+    if(temp == 0){
+        return -1;
+    }
+    PAGE_DIR pml3 = (PAGE_DIR)phys_addr_to_limine_virtual_addr(temp);
+    temp = (uintptr_t)get_pml_entry(pml3, index3, flags);
+    if(temp == 0){
+        return -1;
+    }
+    PAGE_DIR pml2 = (PAGE_DIR)phys_addr_to_limine_virtual_addr(temp);
+    temp = (uintptr_t)get_pml_entry(pml2, index2, flags);
+    if(temp == 0){
+        return -1;
+    }
     PAGE_DIR pml1 = (PAGE_DIR)phys_addr_to_limine_virtual_addr(temp);
 
     pml1[index1] = phys_addr | flags;
@@ -80,12 +102,13 @@ void map_page(PAGE_DIR current_page_directory, uintptr_t phys_addr, uintptr_t vi
         pml4[index4] |= PTE_USER_SUPERVISOR;
     }
     flush_tlb((void *)virt_addr);
+    return 0;
 }
 
 
-void map_page_current(uintptr_t phys_addr, uintptr_t virt_addr, int flags){
+int map_page_current(uintptr_t phys_addr, uintptr_t virt_addr, int flags){
     PAGE_DIR page_directory = (PAGE_DIR)phys_addr_to_limine_virtual_addr(get_cr3());
-    map_page(page_directory, phys_addr, virt_addr, flags);
+    return map_page(page_directory, phys_addr, virt_addr, flags);
 }
 
 void update_cr3(PAGE_DIR current_page_directory){
@@ -104,8 +127,23 @@ uintptr_t get_rip(){
 extern uint8_t limine_start;
 extern uint8_t limine_end;
 
-void vmm_init(uintptr_t kernel_ro_start, uintptr_t kernel_ro_end, uintptr_t kernel_wr_start, uintptr_t kernel_wr_end, uintptr_t initrd_start, uintptr_t initrd_end){
+// The kernel can't run without its page directory, the boot stops
+static void out_of_memory_halt(void){
+    kprintf("Not enough memory to set up paging\n");
+    hcf();
+}
+
+static void map_page_or_halt(PAGE_DIR page_directory, uintptr_t phys_addr, uintptr_t virt_addr, int flags){
+    if(map_page(page_directory, phys_addr, virt_addr, flags) != 0){
+        out_of_memory_halt();
+    }
+}
+
+void vmm_init(uintptr_t kernel_ro_start, uintptr_t kernel_ro_end, uintptr_t kernel_wr_start, uintptr_t kernel_wr_end, struct boot_module *modules, size_t module_count){
     PAGE_DIR root_page_directory = create_page_directory();
+    if(root_page_directory == NULL){
+        out_of_memory_halt();
+    }
     //kprintf("Starting to map virtual memory 0/5\n");
 
     // Identity map the first 4 GB
@@ -115,16 +153,20 @@ void vmm_init(uintptr_t kernel_ro_start, uintptr_t kernel_ro_end, uintptr_t kern
    // }
     kprintf("Mapping virtual memory 1/5\n");
 
-    //map initrd
-    for (uintptr_t i = ALIGN_DOWN(initrd_start, PAGE_SIZE); i < ALIGN_UP(initrd_end, PAGE_SIZE); i += PAGE_SIZE){
-        map_page(root_page_directory, limine_virtual_addr_to_phys_addr(i), i, PTE_PRESENT | PTE_READ_WRITE);
+    //map the modules
+    for (size_t module = 0; module < module_count; module++){
+        uintptr_t module_start = (uintptr_t)modules[module].address;
+        uintptr_t module_end = module_start + modules[module].size;
+        for (uintptr_t i = ALIGN_DOWN(module_start, PAGE_SIZE); i < ALIGN_UP(module_end, PAGE_SIZE); i += PAGE_SIZE){
+            map_page_or_halt(root_page_directory, limine_virtual_addr_to_phys_addr(i), i, PTE_PRESENT | PTE_READ_WRITE);
+        }
     }
     kprintf("Mapping virtual memory 2/5\n");
 
     // Map kernel address space
     for (uintptr_t i = 0; i < 4 * GB; i += PAGE_SIZE){
         
-        map_page(root_page_directory, i, phys_addr_to_limine_virtual_addr(i), PTE_PRESENT | PTE_READ_WRITE);
+        map_page_or_halt(root_page_directory, i, phys_addr_to_limine_virtual_addr(i), PTE_PRESENT | PTE_READ_WRITE);
     }
 
     PAGE_DIR old_root_page_directory = (PAGE_DIR)phys_addr_to_limine_virtual_addr(get_cr3());
@@ -137,7 +179,7 @@ void vmm_init(uintptr_t kernel_ro_start, uintptr_t kernel_ro_end, uintptr_t kern
     // map limine request section
     for(uintptr_t virt_addr = ALIGN_DOWN(limine_start_addr, PAGE_SIZE); virt_addr < limine_end_addr; virt_addr += PAGE_SIZE){
         uintptr_t phys_addr = (uintptr_t)find_phys_addr(old_root_page_directory, virt_addr);
-        map_page(root_page_directory, phys_addr, virt_addr, PTE_PRESENT | PTE_READ_WRITE);
+        map_page_or_halt(root_page_directory, phys_addr, virt_addr, PTE_PRESENT | PTE_READ_WRITE);
     }
 
     kprintf("Mapping virtual memory 4/5\n");
@@ -145,7 +187,7 @@ void vmm_init(uintptr_t kernel_ro_start, uintptr_t kernel_ro_end, uintptr_t kern
     for(uintptr_t virt_addr = ALIGN_DOWN(kernel_ro_start, PAGE_SIZE); virt_addr < kernel_ro_end; virt_addr += PAGE_SIZE)
     {
         uintptr_t phys_addr = (uintptr_t)find_phys_addr(old_root_page_directory, virt_addr);
-        map_page(root_page_directory, phys_addr, virt_addr, PTE_PRESENT | PTE_READ_WRITE);
+        map_page_or_halt(root_page_directory, phys_addr, virt_addr, PTE_PRESENT | PTE_READ_WRITE);
     }
 
     kprintf("Mapping virtual memory 5/5\n");
@@ -155,7 +197,14 @@ void vmm_init(uintptr_t kernel_ro_start, uintptr_t kernel_ro_end, uintptr_t kern
     for(uintptr_t virt_addr = ALIGN_DOWN(kernel_wr_start, PAGE_SIZE); virt_addr < kernel_wr_end; virt_addr += PAGE_SIZE)
     {
         uintptr_t phys_addr = (uintptr_t)find_phys_addr(old_root_page_directory, virt_addr);
-        map_page(root_page_directory, phys_addr, virt_addr, PTE_PRESENT | PTE_READ_WRITE);
+        map_page_or_halt(root_page_directory, phys_addr, virt_addr, PTE_PRESENT | PTE_READ_WRITE);
+    }
+
+    // Allocate every entry of the kernel half so that it can be shared with the user page directories
+    for(uintptr_t index = 256; index < 512; index++){
+        if(get_pml_entry(root_page_directory, index, PTE_PRESENT | PTE_READ_WRITE) == 0){
+            out_of_memory_halt();
+        }
     }
 
     new_cr3_value = limine_virtual_addr_to_phys_addr(root_page_directory);
@@ -169,6 +218,30 @@ void slave_core_init_vmm(){
     update_cr3(new_cr3_value);
 };
 
+// Create a page directory with an empty user half and the kernel half of the root page directory
+// Returns the physical address of the new pml4 or 0 if there is not enough memory
+uintptr_t create_user_page_directory(){
+    PAGE_DIR kernel_page_directory = (PAGE_DIR)phys_addr_to_limine_virtual_addr((uintptr_t)new_cr3_value);
+    PAGE_DIR page_directory = create_page_directory();
+    if(page_directory == NULL){
+        return 0;
+    }
+    for(uintptr_t index = 256; index < 512; index++){
+        page_directory[index] = kernel_page_directory[index];
+    }
+    return limine_virtual_addr_to_phys_addr((uintptr_t)page_directory);
+}
+
+uintptr_t get_kernel_page_directory(){
+    return (uintptr_t)new_cr3_value;
+}
+
+// Returns 0 on success or -1 if there is not enough memory for the page tables
+int map_page_in(uintptr_t pml4_phys_addr, uintptr_t phys_addr, uintptr_t virt_addr, int flags){
+    PAGE_DIR page_directory = (PAGE_DIR)phys_addr_to_limine_virtual_addr(pml4_phys_addr);
+    return map_page(page_directory, phys_addr, virt_addr, flags);
+}
+
 PAGE_DIR get_pml_entry_if_exists(PAGE_DIR pml, uintptr_t index){
     if(pml[index] & 1){
         //entry exist
@@ -214,9 +287,21 @@ void *find_phys_addr(PAGE_DIR pml4, uintptr_t virt_addr){
     uintptr_t index2 = (virt_addr & ((uintptr_t)0x1ff << 21)) >> 21;
     uintptr_t index1 = (virt_addr & ((uintptr_t)0x1ff << 12)) >> 12;
 
-    PAGE_DIR pml3 = (PAGE_DIR)phys_addr_to_limine_virtual_addr((uintptr_t)get_pml_entry(pml4, index4, 0));
-    PAGE_DIR pml2 = (PAGE_DIR)phys_addr_to_limine_virtual_addr((uintptr_t)get_pml_entry(pml3, index3, 0));
-    PAGE_DIR pml1 = (PAGE_DIR)phys_addr_to_limine_virtual_addr((uintptr_t)get_pml_entry(pml2, index2, 0));
+    PAGE_DIR pml3 = get_pml_entry_if_exists(pml4, index4);
+    if(pml3 == 0){
+        return NULL;
+    }
+    pml3 = (PAGE_DIR)phys_addr_to_limine_virtual_addr((uintptr_t)pml3);
+    PAGE_DIR pml2 = get_pml_entry_if_exists(pml3, index3);
+    if(pml2 == 0){
+        return NULL;
+    }
+    pml2 = (PAGE_DIR)phys_addr_to_limine_virtual_addr((uintptr_t)pml2);
+    PAGE_DIR pml1 = get_pml_entry_if_exists(pml2, index2);
+    if(pml1 == 0){
+        return NULL;
+    }
+    pml1 = (PAGE_DIR)phys_addr_to_limine_virtual_addr((uintptr_t)pml1);
     
     uintptr_t phys_addr = pml1[index1] & ~(2047);
     phys_addr |= (virt_addr) & 0xfff;
@@ -224,4 +309,89 @@ void *find_phys_addr(PAGE_DIR pml4, uintptr_t virt_addr){
     phys_addr &= ~(1ll<<63);
 
     return (void *) phys_addr;
+}
+
+// Copy the user half of a page directory, every mapped page is duplicated
+// Returns the physical address of the new pml4 or 0 if there is not enough memory
+uintptr_t clone_user_page_directory(uintptr_t pml4_phys_addr){
+    PAGE_DIR source_pml4 = (PAGE_DIR)phys_addr_to_limine_virtual_addr(pml4_phys_addr);
+    uintptr_t new_pml4 = create_user_page_directory();
+    if(new_pml4 == 0){
+        return 0;
+    }
+
+    for(uintptr_t index4 = 0; index4 < 256; index4++){
+        if(!(source_pml4[index4] & PTE_PRESENT))
+            continue;
+        PAGE_DIR pml3 = (PAGE_DIR)phys_addr_to_limine_virtual_addr(source_pml4[index4] & PTE_ADDR_MASK);
+
+        for(uintptr_t index3 = 0; index3 < 512; index3++){
+            if(!(pml3[index3] & PTE_PRESENT))
+                continue;
+            PAGE_DIR pml2 = (PAGE_DIR)phys_addr_to_limine_virtual_addr(pml3[index3] & PTE_ADDR_MASK);
+
+            for(uintptr_t index2 = 0; index2 < 512; index2++){
+                if(!(pml2[index2] & PTE_PRESENT))
+                    continue;
+                PAGE_DIR pml1 = (PAGE_DIR)phys_addr_to_limine_virtual_addr(pml2[index2] & PTE_ADDR_MASK);
+
+                for(uintptr_t index1 = 0; index1 < 512; index1++){
+                    uint64_t entry = pml1[index1];
+                    if(!(entry & PTE_PRESENT))
+                        continue;
+
+                    uintptr_t page = (uintptr_t)alloc_page_phys_addr(1);
+                    if(page == 0){
+                        // Give back the tables and pages copied so far
+                        free_user_page_directory(new_pml4);
+                        return 0;
+                    }
+
+                    memcpy((void *)phys_addr_to_limine_virtual_addr(page), (void *)phys_addr_to_limine_virtual_addr(entry & PTE_ADDR_MASK), PAGE_SIZE);
+                    uintptr_t virt_addr = (index4 << 39) | (index3 << 30) | (index2 << 21) | (index1 << 12);
+                    if(map_page_in(new_pml4, page, virt_addr, entry & PTE_FLAGS_MASK) != 0){
+                        // The page isn't in the new page directory yet
+                        free_pages((void *)phys_addr_to_limine_virtual_addr(page), 1);
+                        free_user_page_directory(new_pml4);
+                        return 0;
+                    }
+                }
+            }
+        }
+    }
+
+    return new_pml4;
+}
+
+// Free every mapped page of the user half, the page tables and the pml4 itself
+// The page directory must not be the current one
+void free_user_page_directory(uintptr_t pml4_phys_addr){
+    PAGE_DIR pml4 = (PAGE_DIR)phys_addr_to_limine_virtual_addr(pml4_phys_addr);
+
+    for(uintptr_t index4 = 0; index4 < 256; index4++){
+        if(!(pml4[index4] & PTE_PRESENT))
+            continue;
+        PAGE_DIR pml3 = (PAGE_DIR)phys_addr_to_limine_virtual_addr(pml4[index4] & PTE_ADDR_MASK);
+
+        for(uintptr_t index3 = 0; index3 < 512; index3++){
+            if(!(pml3[index3] & PTE_PRESENT))
+                continue;
+            PAGE_DIR pml2 = (PAGE_DIR)phys_addr_to_limine_virtual_addr(pml3[index3] & PTE_ADDR_MASK);
+
+            for(uintptr_t index2 = 0; index2 < 512; index2++){
+                if(!(pml2[index2] & PTE_PRESENT))
+                    continue;
+                PAGE_DIR pml1 = (PAGE_DIR)phys_addr_to_limine_virtual_addr(pml2[index2] & PTE_ADDR_MASK);
+
+                for(uintptr_t index1 = 0; index1 < 512; index1++){
+                    if(pml1[index1] & PTE_PRESENT)
+                        free_pages((void *)phys_addr_to_limine_virtual_addr(pml1[index1] & PTE_ADDR_MASK), 1);
+                }
+                free_pages(pml1, 1);
+            }
+            free_pages(pml2, 1);
+        }
+        free_pages(pml3, 1);
+    }
+    free_pages(pml4, 1);
 }
\ No newline at end of file
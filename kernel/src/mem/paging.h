@@ -2,6 +2,8 @@
 #define __PAGING_H__
 
 #include <stdint.h>
+#include <stddef.h>
+#include "../rust_export.h"
 
 
 #define GB 0x40000000UL
@@ -17,13 +19,21 @@
 #define PTE_PAT		    128
 #define PTE_GLOBAL	    256
 
+#define PTE_ADDR_MASK	    0x000FFFFFFFFFF000UL
+#define PTE_FLAGS_MASK	    0xFFF
+
 typedef uint64_t *PAGE_DIR;
 
-void vmm_init(uintptr_t kernel_ro_start, uintptr_t kernel_ro_end, uintptr_t kernel_wr_start, uintptr_t kernel_wr_end, uintptr_t initrd_start, uintptr_t initrd_end);
+void vmm_init(uintptr_t kernel_ro_start, uintptr_t kernel_ro_end, uintptr_t kernel_wr_start, uintptr_t kernel_wr_end, struct boot_module *modules, size_t module_count);
 void *find_phys_addr(PAGE_DIR pml4, uintptr_t virt_addr);
-void map_page_kernel(uintptr_t phys_addr, uintptr_t virt_addr, int flags);
-void map_page(PAGE_DIR current_page_directory, uintptr_t phys_addr, uintptr_t virt_addr, int flags);
-void map_page_current(uintptr_t phys_addr, uintptr_t virt_addr, int flags);
+int map_page_kernel(uintptr_t phys_addr, uintptr_t virt_addr, int flags);
+int map_page(PAGE_DIR current_page_directory, uintptr_t phys_addr, uintptr_t virt_addr, int flags);
+int map_page_current(uintptr_t phys_addr, uintptr_t virt_addr, int flags);
 void slave_core_init_vmm();
+uintptr_t create_user_page_directory();
+int map_page_in(uintptr_t pml4_phys_addr, uintptr_t phys_addr, uintptr_t virt_addr, int flags);
+uintptr_t clone_user_page_directory(uintptr_t pml4_phys_addr);
+void free_user_page_directory(uintptr_t pml4_phys_addr);
+uintptr_t get_kernel_page_directory();
 
 #endif
\ No newline at end of file
@@ -27,6 +27,27 @@ struct physical_mem_info{
 struct physical_mem_info info;
 BITMAP_t bitmap;
 
+// The bitmap is changed by every core, the entry points below hold this lock.
+// Interrupts are disabled while it is held so a handler can't deadlock on the same core
+static bool pmm_locked = false;
+
+static uint64_t pmm_lock(void){
+    uint64_t flags;
+    __asm__ volatile("pushfq; pop %0; cli" : "=r"(flags) : : "memory");
+    while(__atomic_test_and_set(&pmm_locked, __ATOMIC_ACQUIRE)){
+        __asm__ volatile("pause");
+    }
+    return flags;
+}
+
+static void pmm_unlock(uint64_t flags){
+    __atomic_clear(&pmm_locked, __ATOMIC_RELEASE);
+    // Interrupt flag
+    if(flags & (1 << 9)){
+        __asm__ volatile("sti");
+    }
+}
+
 void pmm_init(){
     struct limine_memmap_entry *current_entry;
 
@@ -112,13 +133,16 @@ uint8_t bitmap_get_bit(BITMAP_t *bitmap, int bit){
 void free_pages(void *pointer, size_t page_count){
     size_t index = limine_virtual_addr_to_phys_addr((uintptr_t)pointer) / PAGE_SIZE;
 
+    uint64_t flags = pmm_lock();
     for(size_t i = 0; i < page_count; i++){
         bitmap_clear_bit(&bitmap, index + i);
     }
 
     info.used_pages -= page_count;
+    pmm_unlock(flags);
 }
 
+// The caller must hold the lock
 void *find_free_pages(size_t requested_count){
     if(requested_count == 0){
         return NULL;
@@ -147,9 +171,11 @@ void *find_free_pages(size_t requested_count){
 
 
 void *alloc_page_phys_addr(size_t page_count){
+    uint64_t flags = pmm_lock();
     void *pointer = find_free_pages(page_count);
 
     if(pointer == NULL){
+        pmm_unlock(flags);
         return NULL;
     }
 
@@ -160,16 +186,24 @@ void *alloc_page_phys_addr(size_t page_count){
     }
 
     info.used_pages += page_count;
+    pmm_unlock(flags);
 
     return (void *)(index * PAGE_SIZE);
 }
 
+// Returns the HHDM address of the pages or NULL if there is not enough memory
 void *alloc_page(size_t page_count){
-    return (void*)phys_addr_to_limine_virtual_addr((uintptr_t)alloc_page_phys_addr(page_count));
+    uintptr_t phys_addr = (uintptr_t)alloc_page_phys_addr(page_count);
+    if(phys_addr == 0){
+        return NULL;
+    }
+    return (void*)phys_addr_to_limine_virtual_addr(phys_addr);
 }
 
 void manually_alloc_page(void *ptr){
     uintptr_t addr = (uintptr_t) ptr;
     uintptr_t page = ALIGN_DOWN(addr, PAGE_SIZE);
+    uint64_t flags = pmm_lock();
     bitmap_set_bit(&bitmap, PAGE_TO_BIT(page));
+    pmm_unlock(flags);
 }
\ No newline at end of file
@@ -3,18 +3,35 @@
 
 #include "io.h"
 
+// A file loaded by Limine, cmdline is the module_cmdline given in limine.conf
+struct boot_module{
+    void *address;
+    uint64_t size;
+    const char *path;
+    const char *cmdline;
+};
+
 void kputs(char *s);
 void kputc(char c);
-void map_page_kernel(uintptr_t phys_addr, uintptr_t virt_addr, int flags);
-void map_page_current(uintptr_t phys_addr, uintptr_t virt_addr, int flags);
+int map_page_kernel(uintptr_t phys_addr, uintptr_t virt_addr, int flags);
+int map_page_current(uintptr_t phys_addr, uintptr_t virt_addr, int flags);
 void *alloc_page(size_t page_count);
 void *alloc_page_phys_addr(size_t page_count);
+void free_pages(void *pointer, size_t page_count);
 void manually_alloc_page(void *ptr);
 uintptr_t phys_addr_to_limine_virtual_addr(uintptr_t phys_addr);
 void start_slave_core(void);
-void usermode_switch(uintptr_t addr, uintptr_t sp);
+void usermode_resume(const void *frame);
+void syscall_entry(void);
 uintptr_t find_page_entry(uintptr_t virt_addr);
+uintptr_t create_user_page_directory(void);
+int map_page_in(uintptr_t pml4_phys_addr, uintptr_t phys_addr, uintptr_t virt_addr, int flags);
+uintptr_t clone_user_page_directory(uintptr_t pml4_phys_addr);
+void free_user_page_directory(uintptr_t pml4_phys_addr);
+uintptr_t get_kernel_page_directory(void);
 
 void move_cursor(size_t x, size_t y);
+extern size_t char_per_row;
+extern size_t rows_count;
 
 #endif
\ No newline at end of file
@@ -1,30 +1,23 @@
-global usermode_switch
+global usermode_resume
 extern set_tss_rsp
+extern interrupt_return
 
-usermode_switch:
-  mov r9, rsi
-  ; Set the segment registers to appropriate values for user mode
-  mov rax, 0x43 ; user data segment
-  mov ds, ax
-  mov es, ax
-  mov fs, ax
-  mov gs, ax
+; Offsets in PerCpu (see rust-kernel/src/percpu.rs)
+PERCPU_KERNEL_STACK equ 8
+PERCPU_CPU_ID equ 24
 
-  ;mov rax, 0x3B ; user code segment
-  mov rax, rsp ; save stack pointer
-  mov r10, rdi
+; rdi: pointer to the interrupt frame (see interrupt_stack in interrupts/interrupts.h) to resume
+; The current stack becomes the kernel stack of the core, used by the interrupts coming from user mode
+usermode_resume:
+  cli ; the stack will point to the frame until iretq
 
-  mov rdi, 0
-  mov rsi, rax
+  mov rbx, rdi
+
+  mov [gs:PERCPU_KERNEL_STACK], rsp
+  mov edi, [gs:PERCPU_CPU_ID]
+  mov rsi, rsp
   mov rdx, 0
   call set_tss_rsp
 
-  mov r8, 0x43
-  push r8
-  push r9
-  mov rax, 0x202
-  push rax
-  mov rax, 0x3B 
-  push rax
-  push r10
-  iretq
\ No newline at end of file
+  mov rsp, rbx
+  jmp interrupt_return
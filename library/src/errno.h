@@ -0,0 +1,30 @@
+#ifndef __MYOS_ERRNO_H__
+#define __MYOS_ERRNO_H__
+
+// Syscalls return the negated value on failure (see kernel/rust-kernel/src/errno.rs)
+#define EPERM   1
+#define ENOENT  2
+#define ESRCH   3
+#define EIO     5
+#define ENOEXEC 8
+#define EBADF   9
+#define ECHILD  10
+#define ENOMEM  12
+#define EFAULT  14
+#define EBUSY   16
+#define EEXIST  17
+#define ENODEV  19
+#define ENOTDIR 20
+#define EISDIR  21
+#define EINVAL  22
+#define EMFILE  24
+#define EFBIG   27
+#define ENOSPC  28
+#define EROFS   30
+#define ERANGE  34
+#define ENAMETOOLONG 36
+#define ENOSYS  38
+#define ENOTEMPTY 39
+#define ELOOP   40
+
+#endif
@@ -9,6 +9,16 @@ char parse_input(unsigned char input){
     }
 }
 
+extern int64_t sys_memalign(uintptr_t size, uintptr_t align);
+
+void *memalign(uintptr_t size, uintptr_t align){
+    int64_t result = sys_memalign(size, align);
+    if(result < 0){
+        return 0;
+    }
+    return (void *)result;
+}
+
 void *malloc(uintptr_t size){
     if(size == 0){
         return 0;
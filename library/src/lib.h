@@ -1,15 +1,71 @@
 #ifndef __MYOS_LIB_H__
 #define __MYOS_LIB_H__
 #include <stdint.h>
+#include "errno.h"
 
 void print(char *);
 void exit(unsigned int);
-char input();
-void *memalign(uintptr_t size, uintptr_t align);
+char input(); // blocks until a key is pressed
+void *memalign(uintptr_t size, uintptr_t align); // 0 on failure
 void *malloc(uintptr_t size);
 void free(void *);
 void putc(char);
 void move_cursor(size_t x, size_t y);
+// Return a negative error code on failure (see errno.h)
+int64_t spawn(char *path);
+int64_t exec(char *path);
+int64_t fork();
+int64_t waitpid(int64_t pid);
+void sleep(uint64_t ms);
+
+#define O_RDONLY 0
+#define O_WRONLY 1
+#define O_RDWR   2
+#define O_CREAT  0x40
+#define O_TRUNC  0x200
+#define O_APPEND 0x400
+
+#define SEEK_SET 0
+#define SEEK_CUR 1
+#define SEEK_END 2
+
+#define FILE_TYPE_REGULAR 1
+#define FILE_TYPE_FOLDER  2
+#define FILE_TYPE_SYMLINK 3
+
+struct stat {
+    uint64_t size;
+    uint64_t file_type;
+    uint64_t mode; // permission bits, 0755 is rwxr-xr-x
+    uint64_t uid;
+    uint64_t gid;
+    uint64_t mtime; // seconds since the Unix epoch
+};
+
+int64_t open(char *path, int64_t flags);
+int64_t read(int64_t fd, void *buffer, uint64_t len); // 0 at the end of the file
+int64_t write(int64_t fd, void *buffer, uint64_t len);
+int64_t close(int64_t fd);
+int64_t lseek(int64_t fd, int64_t offset, int64_t whence);
+int64_t fstat(int64_t fd, struct stat *stat);
+int64_t ftruncate(int64_t fd, uint64_t size);
+int64_t mkdir(char *path);
+int64_t unlink(char *path); // also removes empty folders
+int64_t mount(char *target, char *fs_type); // only "tmpfs" is supported
+int64_t umount(char *target);
+// Relative paths given to the other syscalls start from the working folder
+int64_t chdir(char *path);
+int64_t getcwd(char *buffer, uint64_t size); // ERANGE if the buffer is too small
+
+// Records written by getdents, the next one starts reclen bytes after the current one
+struct dirent {
+    uint32_t reclen;
+    uint32_t file_type;
+    char name[]; // null terminated
+};
+
+// Returns the number of bytes written in buffer, 0 once the whole folder has been read
+int64_t getdents(int64_t fd, void *buffer, uint64_t len);
 
 char parse_input(unsigned char);
 
@@ -1,48 +1,196 @@
 global print
 global exit
 global input
-global memalign
+global sys_memalign
+global spawn
+global exec
+global fork
+global waitpid
+global sleep
+global open
+global read
+global close
+global lseek
+global fstat
+global getdents
+global write
+global mkdir
+global unlink
+global ftruncate
+global mount
+global umount
+global chdir
+global getcwd
 
+; syscall clobbers rcx and r11, they are caller saved so the wrappers don't need to keep them
+; The kernel still accepts int 0x40 with the same registers
+; rdi: syscall number, rsi, rdx, r10: arguments
 print:
     mov rsi, rdi
     mov rdi, 1
-    int 0x40
+    syscall
     ret
 
 exit:
     mov rsi, rdi
     mov rdi, 2
-    int 0x40
+    syscall
     ; This syscall should not return
     ret ; in case it fail (should not happens)
 
 input:
     mov rdi, 3
-    int 0x40
+    syscall
     ret
 
-memalign:
+sys_memalign:
     mov rdx, rsi
     mov rsi, rdi
     mov rdi, 4
-    int 0x40
+    syscall
     ret
 
 free:
     mov rsi, rdi
     mov rdi, 5
-    int 0x40
+    syscall
     ret
 
 putc:
     mov rsi, rdi
     mov rdi, 8
-    int 0x40
+    syscall
     ret
 
 move_cursor
     mov rdx, rsi
     mov rsi, rdi
     mov rdi, 7
-    int 0x40
-    ret
\ No newline at end of file
+    syscall
+    ret
+
+spawn:
+    mov rsi, rdi
+    mov rdi, 9
+    syscall
+    ret
+
+exec:
+    mov rsi, rdi
+    mov rdi, 10
+    syscall
+    ret ; only returns if it failed
+
+fork:
+    mov rdi, 11
+    syscall
+    ret
+
+waitpid:
+    mov rsi, rdi
+    mov rdi, 12
+    syscall
+    ret
+
+sleep:
+    mov rsi, rdi
+    mov rdi, 13
+    syscall
+    ret
+
+open:
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 6
+    syscall
+    ret
+
+read:
+    mov r10, rdx
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 14
+    syscall
+    ret
+
+close:
+    mov rsi, rdi
+    mov rdi, 15
+    syscall
+    ret
+
+lseek:
+    mov r10, rdx
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 16
+    syscall
+    ret
+
+fstat:
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 17
+    syscall
+    ret
+
+getdents:
+    mov r10, rdx
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 18
+    syscall
+    ret
+
+write:
+    mov r10, rdx
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 19
+    syscall
+    ret
+
+mkdir:
+    mov rsi, rdi
+    mov rdi, 20
+    syscall
+    ret
+
+unlink:
+    mov rsi, rdi
+    mov rdi, 21
+    syscall
+    ret
+
+ftruncate:
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 22
+    syscall
+    ret
+
+mount:
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 23
+    syscall
+    ret
+
+umount:
+    mov rsi, rdi
+    mov rdi, 24
+    syscall
+    ret
+
+chdir:
+    mov rsi, rdi
+    mov rdi, 25
+    syscall
+    ret
+
+getcwd:
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 26
+    syscall
+    ret